@@ -0,0 +1,39 @@
+//! Recognition robustness evaluation
+//!
+//! Indexes given mp3 files in memory, cuts queries from them, distorts the queries
+//! with default degradations and reports recall, precision and time to identify
+//! for each degradation. Queries and noise come from a fixed seed,
+//! so reports of different fingerprint configurations can be compared.
+//!
+//! Run with: `cargo run --release --example evaluate <mp3 file> [mp3 file ...]`
+//!
+use clatter_rattles_rs::evaluation::Evaluation;
+use clatter_rattles_rs::fingerprint::{FingerprintConfig, FingerprintHandle};
+use clatter_rattles_rs::helpers::decode_mp3_from_file;
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let references: Vec<_> = env::args()
+        .skip(1)
+        .map(|path| {
+            let song = Path::new(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone());
+            let decoded = decode_mp3_from_file(&path).expect("mp3 file can not be decoded");
+            (song, decoded)
+        })
+        .collect();
+    if references.is_empty() {
+        println!("usage: evaluate <mp3 file> [mp3 file ...]");
+        return;
+    }
+
+    let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+    let reports = Evaluation::default().run(&handle, &references).unwrap();
+    println!("{} references", references.len());
+    for report in reports.iter() {
+        println!("{}", report);
+    }
+}
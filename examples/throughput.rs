@@ -0,0 +1,76 @@
+//! Fingerprinting throughput benchmark
+//!
+//! Fingerprints the same synthetic stream several times and reports
+//! how many seconds of audio are fingerprinted per second of wall clock time.
+//! Stream is generated from a fixed seed in the canonical sample rate,
+//! so resampling is left out and every run processes exactly the same samples.
+//!
+//! Run with: `cargo run --release --example throughput [seconds of audio] [runs]`
+//!
+use clatter_rattles_rs::fingerprint::{FingerprintConfig, FingerprintHandle};
+use clatter_rattles_rs::helpers::AudioBuffer;
+use rand::prelude::*;
+use std::env;
+use std::f32::consts::PI;
+use std::time::Instant;
+
+const SEED: u64 = 2020; // fixed seed, so every run benchmarks the same stream
+const SECONDS: usize = 600; // default length of the stream in seconds
+const RUNS: usize = 5; // default number of timed runs
+
+fn synthetic_stream(seconds: usize, sample_rate: u32) -> AudioBuffer {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let tone_length = sample_rate as usize / 8;
+    let mut samples = Vec::with_capacity(seconds * sample_rate as usize);
+    while samples.len() < seconds * sample_rate as usize {
+        let low = rng.gen_range(100.0, 800.0);
+        let high = rng.gen_range(800.0, 4000.0);
+        samples.extend((0..tone_length).map(|t| {
+            let time = t as f32 / sample_rate as f32;
+            (2.0 * PI * low * time).sin() * 6000.0
+                + (2.0 * PI * high * time).sin() * 3000.0
+                + rng.gen::<f32>() * 2000.0
+                - 1000.0
+        }));
+    }
+    samples.truncate(seconds * sample_rate as usize);
+    AudioBuffer::new(samples, sample_rate)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let seconds = args
+        .next()
+        .map(|arg| arg.parse().expect("seconds of audio must be a number"))
+        .unwrap_or(SECONDS);
+    let runs = args
+        .next()
+        .map(|arg| arg.parse().expect("number of runs must be a number"))
+        .unwrap_or(RUNS);
+    assert!(runs > 0, "number of runs must be greater than zero");
+
+    let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+    let stream = synthetic_stream(seconds, handle.config().sample_rate);
+    let landmarks = handle.calc_fingerprint_collection(&stream).unwrap().len(); // warm up
+
+    let mut rates: Vec<f64> = (0..runs)
+        .map(|_| {
+            let start_time = Instant::now();
+            handle.calc_fingerprint_collection(&stream).unwrap();
+            seconds as f64 / start_time.elapsed().as_secs_f64()
+        })
+        .collect();
+    rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    println!(
+        "{} s of audio, {} landmarks, {} threads",
+        seconds,
+        landmarks,
+        rayon::current_num_threads()
+    );
+    println!(
+        "seconds of audio fingerprinted per second: median {:.0}, best {:.0}, worst {:.0}",
+        rates[rates.len() / 2],
+        rates[rates.len() - 1],
+        rates[0]
+    );
+}
@@ -0,0 +1,42 @@
+use super::Landmark;
+use crate::fingerprint::Hash;
+
+/// Author and title of the first fake song
+pub const SONG_1: &str = "Shrek and Donkey - Pinocchio is lying again";
+/// Author and title of the second fake song
+pub const SONG_2: &str = "Alice in Wonderland - Poker face";
+
+/// Landmarks with given hashes at consecutive offsets
+///
+/// # Arguments:
+/// * hashes - hash of each landmark, its position is the landmark offset
+///
+/// # Returns collection of landmarks in the order of hashes
+///
+pub fn fake_landmarks(hashes: &[u32]) -> Vec<Landmark> {
+    hashes
+        .iter()
+        .enumerate()
+        .map(|(offset, hash)| Landmark {
+            hash: Hash::from(*hash),
+            offset,
+        })
+        .collect()
+}
+
+/// Landmarks of two fake songs sharing the last 7 hashes at the same offsets
+///
+/// # Returns landmarks of the first and of the second song
+///
+pub fn fake_fingerprints() -> (Vec<Landmark>, Vec<Landmark>) {
+    (
+        fake_landmarks(&[
+            1234567890, 1987654321, 1290347856, 1111111111, 2222222222, 3333333333, 444444444,
+            555555555, 666666666, 777777777,
+        ]),
+        fake_landmarks(&[
+            1231231234, 999999999, 888888888, 1111111111, 2222222222, 3333333333, 444444444,
+            555555555, 666666666, 777777777,
+        ]),
+    )
+}
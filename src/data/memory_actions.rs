@@ -0,0 +1,181 @@
+use super::Repository;
+use crate::fingerprint::{AlgorithmId, FingerprintConfig, Hash, Landmark};
+use crate::helpers::{OffsetVotes, SongMatch};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Repository held in process memory
+///
+/// Indexes landmarks the same way as `RedisHelper`, so matching can be evaluated
+/// and tested without a database, at the cost of keeping the whole index in memory
+/// and losing it when the process ends.
+///
+#[derive(Clone, Debug, Default)]
+pub struct MemoryRepository {
+    /// Song index and landmark offset of each hash
+    index: HashMap<Hash, Vec<(usize, usize)>>,
+    /// Author and title of indexed songs
+    songs: Vec<String>,
+    /// Configuration the fingerprints were calculated with
+    config: Option<FingerprintConfig>,
+    /// Identifier of the algorithm the fingerprints were calculated with
+    algorithm: Option<AlgorithmId>,
+}
+
+impl MemoryRepository {
+    /// Create new instance of empty MemoryRepository
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of indexed songs
+    ///
+    pub fn songs(&self) -> usize {
+        self.songs.len()
+    }
+
+    /// Votes for time offset between the sample and each song sharing its hashes
+    ///
+    fn offset_votes(&self, fingerprints: &[Landmark]) -> OffsetVotes {
+        let mut votes = OffsetVotes::new();
+        for landmark in fingerprints.iter() {
+            if let Some(entries) = self.index.get(&landmark.hash) {
+                for (song, offset) in entries.iter() {
+                    votes.vote(&self.songs[*song], *offset, landmark.offset);
+                }
+            }
+        }
+        votes
+    }
+}
+
+impl Repository for MemoryRepository {
+    fn store(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+        song: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_compatibility(config)?;
+        if self.algorithm.is_none() {
+            self.config = Some(config.clone());
+            self.algorithm = Some(AlgorithmId::new(config));
+        }
+        let song = match self.songs.iter().position(|indexed| indexed == song) {
+            Some(song) => song,
+            None => {
+                self.songs.push(song.to_string());
+                self.songs.len() - 1
+            }
+        };
+        for landmark in fingerprints.iter() {
+            let entries = self.index.entry(landmark.hash).or_default();
+            // the same landmark stored twice is kept once, as in a set of redis repository
+            if !entries.contains(&(song, landmark.offset)) {
+                entries.push((song, landmark.offset));
+            }
+        }
+        Ok(())
+    }
+
+    fn load_config(&mut self) -> Result<Option<FingerprintConfig>, Box<dyn Error>> {
+        Ok(self.config.clone())
+    }
+
+    fn load_algorithm(&mut self) -> Result<Option<AlgorithmId>, Box<dyn Error>> {
+        Ok(self.algorithm)
+    }
+
+    fn find_matches(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+    ) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        self.check_compatibility(config)?;
+        Ok(self.offset_votes(fingerprints).scores())
+    }
+
+    fn locate_matches(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+    ) -> Result<Vec<SongMatch>, Box<dyn Error>> {
+        self.check_compatibility(config)?;
+        Ok(self.offset_votes(fingerprints).song_matches(config))
+    }
+
+    fn matching_landmarks(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+        song: &str,
+    ) -> Result<Vec<Landmark>, Box<dyn Error>> {
+        self.check_compatibility(config)?;
+        let song = match self.songs.iter().position(|indexed| indexed == song) {
+            Some(song) => song,
+            None => return Ok(Vec::new()),
+        };
+        Ok(fingerprints
+            .iter()
+            .filter(|landmark| match self.index.get(&landmark.hash) {
+                Some(entries) => entries.iter().any(|(indexed, _)| *indexed == song),
+                None => false,
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::fixtures::{fake_fingerprints, SONG_1, SONG_2};
+    use super::{FingerprintConfig, MemoryRepository, Repository};
+
+    #[test]
+    fn test_repository() {
+        let (fake_fingerprints_1, fake_fingerprints_2) = fake_fingerprints();
+        let song_1 = SONG_1.to_string();
+        let song_2 = SONG_2.to_string();
+        let config = FingerprintConfig::default();
+        let mut repository = MemoryRepository::new();
+        assert_eq!(repository.load_config().unwrap(), None);
+        repository
+            .store(&config, &fake_fingerprints_1, &song_1)
+            .unwrap();
+        repository
+            .store(&config, &fake_fingerprints_2, &song_2)
+            .unwrap();
+        repository
+            .store(&config, &fake_fingerprints_2, &song_2)
+            .unwrap();
+        assert_eq!(repository.songs(), 2);
+        assert_eq!(repository.load_config().unwrap(), Some(config.clone()));
+
+        let matches = repository
+            .find_matches(&config, &fake_fingerprints_2)
+            .unwrap();
+        assert_eq!(matches[&song_1], 7);
+        assert_eq!(matches[&song_2], 10);
+        let located = repository
+            .locate_matches(&config, &fake_fingerprints_2)
+            .unwrap();
+        assert_eq!(located[0].song, song_2);
+        assert_eq!(located[1].score, 7);
+        let matching = repository
+            .matching_landmarks(&config, &fake_fingerprints_2, &song_1)
+            .unwrap();
+        assert_eq!(matching, fake_fingerprints_2[3..].to_vec());
+
+        let incompatible = FingerprintConfig {
+            fuzz_factor: 4,
+            ..config
+        };
+        assert!(repository
+            .store(&incompatible, &fake_fingerprints_1, &song_1)
+            .is_err());
+        assert!(repository
+            .find_matches(&incompatible, &fake_fingerprints_2)
+            .is_err());
+    }
+}
@@ -1,34 +1,193 @@
-use super::helpers::decode_mp3_from_chunk;
+use super::fingerprint::{AlgorithmId, FingerprintConfig, FingerprintFile, Landmark, SpeedVariant};
+use super::helpers::SongMatch;
+#[cfg(test)]
+mod fixtures;
+pub mod memory_actions;
+#[cfg(feature = "redis")]
 pub mod redis_actions;
+#[cfg(feature = "stream")]
 pub mod stream_actions;
 use std::collections::HashMap;
 use std::error::Error;
 
 pub trait Repository {
-    /// Index all given fingerprints by adding or updating / adding fingerprint as a key
-    /// and pushes song to set of values
+    /// Index all given fingerprints by adding or updating / adding fingerprint hash as a key
+    /// and pushes song together with landmark offset to set of values
+    ///
+    /// Configuration and algorithm identifier the fingerprints were calculated with are saved
+    /// on first store, storing fingerprints of incompatible algorithm is refused,
+    /// see `check_compatibility`
     ///
     /// #Arguments:
-    /// * fingerprints - collection of all fingerprints for a given song
+    /// * config - parameters the fingerprints were calculated with
+    /// * fingerprints - collection of all landmarks for a given song
     /// * song - author and title of a song in one string
     ///
     /// # Return success if whole query is done successfully or dynamic Error otherwise
     ///  
-    fn store(&mut self, fingerprints: &Vec<usize>, song: &String) -> Result<(), Box<dyn Error>>;
+    fn store(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+        song: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Reads configuration songs in the repository were indexed with,
+    /// so samples can be fingerprinted with the same parameters
+    ///
+    /// # Returns success of Option with configuration if any song is indexed, or dynamic Error otherwise
+    ///
+    fn load_config(&mut self) -> Result<Option<FingerprintConfig>, Box<dyn Error>>;
 
-    /// Get all fingerprints with corresponding songs list (set), then calculate hash map collection
-    /// by using the song title and author as a key and number of fingerprints it occurred in as a value
+    /// Reads identifier of the algorithm songs in the repository were indexed with
+    ///
+    /// # Returns success of Option with algorithm identifier if any song is indexed
+    /// with a versioned algorithm, or dynamic Error otherwise
+    ///
+    fn load_algorithm(&mut self) -> Result<Option<AlgorithmId>, Box<dyn Error>>;
+
+    /// Checks if landmarks calculated with given configuration can be stored in the repository
+    /// or matched against it
+    ///
+    /// Landmarks calculated by different version of the algorithm, or with different parameters,
+    /// never share hashes with the indexed ones, so matching them would silently find nothing.
+    /// Repository indexed before algorithm identifier was stored is incompatible with any
+    /// configuration, as there is no way to tell how its hashes were calculated.
     ///
     /// # Arguments:
-    /// * fingerprints - collection of all fingerprints that We want to match songs against
+    /// * config - parameters the landmarks are calculated with
     ///
-    /// # Returns success of Hash map representing match count if query result is success,
-    /// or dynamic Error otherwise
+    /// # Returns success if repository is empty or indexed with the same algorithm,
+    /// dynamic Error otherwise
+    ///
+    fn check_compatibility(&mut self, config: &FingerprintConfig) -> Result<(), Box<dyn Error>> {
+        let expected = AlgorithmId::new(config);
+        match self.load_algorithm()? {
+            Some(stored) if stored == expected => Ok(()),
+            Some(stored) => Err(Box::from(format!(
+                "Repository is indexed with fingerprint algorithm {} of configuration {}, got algorithm {} of configuration {}",
+                stored,
+                self.load_config()?.map(|stored| stored.to_string()).unwrap_or_default(),
+                expected,
+                config
+            ))),
+            None => match self.load_config()? {
+                Some(_) => Err(Box::from(
+                    "Repository is indexed with unversioned fingerprint algorithm, songs have to be indexed again",
+                )),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Index fingerprints of a song read from a fingerprint file, the same way as `store`
+    ///
+    /// # Arguments:
+    /// * file - fingerprints of a song together with configuration they were calculated with
+    ///
+    /// # Returns success if whole query is done successfully, or dynamic Error otherwise,
+    /// also if the file is written by a different version of the algorithm
+    ///
+    fn ingest(&mut self, file: &FingerprintFile) -> Result<(), Box<dyn Error>> {
+        file.check_compatibility(&file.config)?;
+        self.store(&file.config, &file.landmarks, &file.song)
+    }
+
+    /// Get all fingerprints with corresponding songs and offsets list (set), then vote for
+    /// time offset between each song and the sample, and calculate hash map collection
+    /// by using the song title and author as a key and number of landmarks aligned
+    /// at the best offset as a value
+    ///
+    /// # Arguments:
+    /// * config - parameters the fingerprints were calculated with
+    /// * fingerprints - collection of all landmarks that We want to match songs against
+    ///
+    /// # Returns success of Hash map representing match score if query result is success,
+    /// or dynamic Error otherwise, also if configuration is incompatible with the repository
     ///
     fn find_matches(
         &mut self,
-        fingerprints: &Vec<usize>,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
     ) -> Result<HashMap<String, usize>, Box<dyn Error>>;
+
+    /// Votes for time offset between each song and the sample the same way as `find_matches`,
+    /// and locates the sample inside each song at the best offset
+    ///
+    /// # Arguments:
+    /// * config - parameters the fingerprints were calculated with
+    /// * fingerprints - collection of all landmarks that We want to match songs against
+    ///
+    /// # Returns success of collection of matches ordered by score with matched part
+    /// of the song and of the sample in seconds, or dynamic Error otherwise,
+    /// also if configuration is incompatible with the repository
+    ///
+    fn locate_matches(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+    ) -> Result<Vec<SongMatch>, Box<dyn Error>>;
+
+    /// Get landmarks of the sample which hashes are indexed for a given song,
+    /// so peaks that matched can be highlighted when diagnosing a missing match
+    ///
+    /// # Arguments:
+    /// * config - parameters the fingerprints were calculated with
+    /// * fingerprints - collection of all landmarks of the sample
+    /// * song - author and title of a song in one string
+    ///
+    /// # Returns success of collection of matching landmarks in the sample order,
+    /// or dynamic Error otherwise
+    ///
+    fn matching_landmarks(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+        song: &str,
+    ) -> Result<Vec<Landmark>, Box<dyn Error>>;
+
+    /// Matches the sample at each of searched playback speeds, so songs played faster
+    /// or slower than they were indexed, as radio stations often do, are still found
+    ///
+    /// # Arguments:
+    /// * config - parameters the fingerprints were calculated with
+    /// * variants - landmarks of the sample calculated for each speed factor
+    ///
+    /// # Returns success of Hash map with the best match score of each song
+    /// and the speed factor it was reached at, or dynamic Error otherwise
+    ///
+    fn find_matches_at_speeds(
+        &mut self,
+        config: &FingerprintConfig,
+        variants: &[SpeedVariant],
+    ) -> Result<HashMap<String, SpeedMatch>, Box<dyn Error>> {
+        let mut matches: HashMap<String, SpeedMatch> = HashMap::new();
+        for variant in variants.iter() {
+            for (song, score) in self.find_matches(config, &variant.landmarks)? {
+                let best = matches.entry(song).or_insert(SpeedMatch {
+                    score,
+                    speed: variant.speed,
+                });
+                if score > best.score {
+                    *best = SpeedMatch {
+                        score,
+                        speed: variant.speed,
+                    };
+                }
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// Match of a song found by speed tolerant matching
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedMatch {
+    /// Number of landmarks aligned at the best offset
+    pub score: usize,
+    /// Estimated playback speed of the sample relative to the song, f.e. 1.03 if sped up by 3%
+    pub speed: f32,
 }
 
 pub trait PlaylistHelper {
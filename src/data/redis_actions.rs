@@ -1,8 +1,13 @@
 use super::Repository;
+use crate::fingerprint::{AlgorithmId, FingerprintConfig, Landmark};
+use crate::helpers::{OffsetVotes, SongMatch};
 use redis::{transaction, Client, Commands, Connection};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 
+const CONFIG_KEY: &str = "clatter_rattles:config"; // key of fingerprint configuration, does not collide with decimal hash keys
+const ALGORITHM_KEY: &str = "clatter_rattles:algorithm"; // key of fingerprint algorithm identifier
+
 pub struct RedisHelper {
     connection: Connection,
 }
@@ -21,72 +26,155 @@ impl RedisHelper {
         let connection = client.get_connection()?;
         Ok(Self { connection })
     }
+
+    /// Votes for time offset between the sample and each song sharing its hashes
+    ///
+    fn offset_votes(&mut self, fingerprints: &[Landmark]) -> Result<OffsetVotes, Box<dyn Error>> {
+        let mut votes = OffsetVotes::new();
+        for landmark in fingerprints.iter() {
+            let members: HashSet<String> = self.connection.smembers(landmark.hash.value())?;
+            for member in members.iter() {
+                let (offset, song) = decode_member(member)?;
+                votes.vote(song, offset, landmark.offset);
+            }
+        }
+        Ok(votes)
+    }
 }
 
 impl Repository for RedisHelper {
-    fn store(&mut self, fingerprints: &Vec<usize>, song: &String) -> Result<(), Box<dyn Error>> {
-        transaction(&mut self.connection, fingerprints, |con, pipe| {
-            for fingerprint in fingerprints.iter() {
-                pipe.sadd(*fingerprint, song.clone()).ignore();
+    fn store(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+        song: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // configuration is written only if the repository is empty, both keys in one command,
+        // then compared, so concurrent first stores of different configurations can not both succeed
+        self.connection.mset_nx::<_, _, bool>(&[
+            (CONFIG_KEY, config.to_string()),
+            (ALGORITHM_KEY, AlgorithmId::new(config).to_string()),
+        ])?;
+        self.check_compatibility(config)?;
+        let hashes: Vec<u32> = fingerprints
+            .iter()
+            .map(|landmark| landmark.hash.value())
+            .collect();
+        transaction(&mut self.connection, &hashes, |con, pipe| {
+            for landmark in fingerprints.iter() {
+                pipe.sadd(landmark.hash.value(), encode_member(landmark.offset, song))
+                    .ignore();
             }
-            pipe.query(con)
+            pipe.query::<Option<()>>(con)
         })?;
         Ok(())
     }
 
     fn find_matches(
         &mut self,
-        fingerprints: &Vec<usize>,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
     ) -> Result<HashMap<String, usize>, Box<dyn Error>> {
-        let mut matches: HashMap<String, usize> = HashMap::new();
-        for fingerprint in fingerprints.iter() {
-            let songs: HashSet<String> = self.connection.smembers(*fingerprint)?;
-            for song in songs.iter() {
-                match matches.get_mut(song) {
-                    Some(count) => {
-                        *count += 1;
-                    }
-                    None => {
-                        matches.insert(song.clone(), 1);
-                    }
-                };
+        self.check_compatibility(config)?;
+        Ok(self.offset_votes(fingerprints)?.scores())
+    }
+
+    fn locate_matches(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+    ) -> Result<Vec<SongMatch>, Box<dyn Error>> {
+        self.check_compatibility(config)?;
+        Ok(self.offset_votes(fingerprints)?.song_matches(config))
+    }
+
+    fn matching_landmarks(
+        &mut self,
+        config: &FingerprintConfig,
+        fingerprints: &[Landmark],
+        song: &str,
+    ) -> Result<Vec<Landmark>, Box<dyn Error>> {
+        self.check_compatibility(config)?;
+        let mut matching = Vec::new();
+        for landmark in fingerprints.iter() {
+            let members: HashSet<String> = self.connection.smembers(landmark.hash.value())?;
+            for member in members.iter() {
+                if decode_member(member)?.1 == song {
+                    matching.push(*landmark);
+                    break;
+                }
             }
         }
-        Ok(matches)
+        Ok(matching)
+    }
+
+    fn load_config(&mut self) -> Result<Option<FingerprintConfig>, Box<dyn Error>> {
+        let config: Option<String> = self.connection.get(CONFIG_KEY)?;
+        match config {
+            Some(config) => Ok(Some(config.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn load_algorithm(&mut self) -> Result<Option<AlgorithmId>, Box<dyn Error>> {
+        let algorithm: Option<String> = self.connection.get(ALGORITHM_KEY)?;
+        match algorithm {
+            Some(algorithm) => Ok(Some(algorithm.parse()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Set member holds song offset in front of the song, as song title may contain a separator
+///
+fn encode_member(offset: usize, song: &str) -> String {
+    format!("{}:{}", offset, song)
+}
+
+fn decode_member(member: &str) -> Result<(usize, &str), Box<dyn Error>> {
+    let mut parts = member.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(offset), Some(song)) => Ok((offset.parse()?, song)),
+        _ => Err(Box::from(format!("Invalid landmark entry: {}", member))),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{RedisHelper, Repository};
+    use super::super::fixtures::{fake_fingerprints, fake_landmarks, SONG_1, SONG_2};
+    use super::{FingerprintConfig, RedisHelper, Repository};
     use std::time::Instant;
+
+    #[test]
+    fn test_member_encoding() {
+        let member = super::encode_member(42, "AC/DC: Live - T.N.T.");
+        assert_eq!(
+            super::decode_member(&member).unwrap(),
+            (42, "AC/DC: Live - T.N.T.")
+        );
+        assert!(super::decode_member("no separator").is_err());
+    }
     #[test]
     // #[ignore]
     fn test_repository() {
         dotenv::dotenv().ok();
         if dotenv!("REDIS_ENABLED") == "true" {
-            let fake_fingerprints_1: Vec<usize> = vec![
-                1234567890, 1987654321, 1290347856, 1111111111, 2222222222, 3333333333, 4444444444,
-                5555555555, 6666666666, 7777777777,
-            ];
-            let fake_fingerprints_2: Vec<usize> = vec![
-                1231231234, 9999999999, 8888888888, 1111111111, 2222222222, 3333333333, 4444444444,
-                5555555555, 6666666666, 7777777777,
-            ];
-            let song_1 = format!("Shrek and Donkey - Pinocchio is lying again");
-            let song_2 = format!("Alice in Wonderland - Poker face");
+            let (fake_fingerprints_1, fake_fingerprints_2) = fake_fingerprints();
+            let song_1 = SONG_1.to_string();
+            let song_2 = SONG_2.to_string();
+            let config = FingerprintConfig::default();
             let mut db_handler = RedisHelper::new(&"redis://127.0.0.1/").unwrap();
-            if let Ok(_) = db_handler.store(&fake_fingerprints_1, &song_1) {
+            if let Ok(_) = db_handler.store(&config, &fake_fingerprints_1, &song_1) {
                 assert_eq!(1, 1);
             } else {
                 assert_eq!(1, 2);
             }
-            if let Ok(_) = db_handler.store(&fake_fingerprints_2, &song_2) {
+            if let Ok(_) = db_handler.store(&config, &fake_fingerprints_2, &song_2) {
                 assert_eq!(1, 1);
             } else {
                 assert_eq!(1, 2);
             }
-            if let Ok(matches) = db_handler.find_matches(&fake_fingerprints_2) {
+            if let Ok(matches) = db_handler.find_matches(&config, &fake_fingerprints_2) {
                 match matches.get(&song_1) {
                     Some(m) => assert_eq!(*m, 7_usize),
                     None => assert_eq!(1, 2),
@@ -98,6 +186,27 @@ mod test {
             } else {
                 assert_eq!(1, 2);
             }
+            let matching = db_handler
+                .matching_landmarks(&config, &fake_fingerprints_2, &song_1)
+                .unwrap();
+            assert_eq!(matching, fake_fingerprints_2[3..].to_vec());
+            let located = db_handler
+                .locate_matches(&config, &fake_fingerprints_2)
+                .unwrap();
+            assert_eq!(located[0].song, song_2);
+            assert_eq!(located[0].score, 10);
+            assert_eq!(located[1].song, song_1);
+            assert_eq!(located[1].song_start, located[1].sample_start);
+            let incompatible = FingerprintConfig {
+                fuzz_factor: 4,
+                ..config.clone()
+            };
+            assert!(db_handler
+                .store(&incompatible, &fake_fingerprints_1, &song_1)
+                .is_err());
+            assert!(db_handler
+                .find_matches(&incompatible, &fake_fingerprints_2)
+                .is_err());
         } else {
             println!("test_repository does nothing");
         }
@@ -107,26 +216,23 @@ mod test {
     fn test_benchmark_repository() {
         dotenv::dotenv().ok();
         if dotenv!("REDIS_ENABLED") == "true" {
-            let mut fake_fingerprints_1: Vec<usize> = Vec::new();
-            let mut fake_fingerprints_2: Vec<usize> = Vec::new();
             let fingerprint_1_max: usize = 20000;
             let fingerprint_2_max: usize = 30000;
-            for fingerprint in 0..fingerprint_1_max {
-                fake_fingerprints_1.push(fingerprint);
-            }
-            for fingerprint in 0..fingerprint_2_max {
-                fake_fingerprints_2.push(fingerprint);
-            }
-            let song_1 = format!("Shrek and Donkey - Pinocchio is lying again");
-            let song_2 = format!("Alice in Wonderland - Poker face");
+            let fake_fingerprints_1 =
+                fake_landmarks(&(0..fingerprint_1_max as u32).collect::<Vec<u32>>());
+            let fake_fingerprints_2 =
+                fake_landmarks(&(0..fingerprint_2_max as u32).collect::<Vec<u32>>());
+            let song_1 = SONG_1.to_string();
+            let song_2 = SONG_2.to_string();
+            let config = FingerprintConfig::default();
             let mut db_handler = RedisHelper::new(&"redis://127.0.0.1/").unwrap();
             let start_time = Instant::now();
-            if let Ok(_) = db_handler.store(&fake_fingerprints_1, &song_1) {
+            if let Ok(_) = db_handler.store(&config, &fake_fingerprints_1, &song_1) {
                 assert_eq!(1, 1);
             } else {
                 assert_eq!(1, 2);
             }
-            if let Ok(_) = db_handler.store(&fake_fingerprints_2, &song_2) {
+            if let Ok(_) = db_handler.store(&config, &fake_fingerprints_2, &song_2) {
                 assert_eq!(1, 1);
             } else {
                 assert_eq!(1, 2);
@@ -137,7 +243,7 @@ mod test {
                 start_time.elapsed().as_millis()
             );
             let half_time = Instant::now();
-            if let Ok(matches) = db_handler.find_matches(&fake_fingerprints_2) {
+            if let Ok(matches) = db_handler.find_matches(&config, &fake_fingerprints_2) {
                 match matches.get(&song_1) {
                     Some(m) => assert_eq!(*m, fingerprint_1_max),
                     None => assert_eq!(1, 2),
@@ -1,8 +1,12 @@
-use super::{decode_mp3_from_chunk, PlaylistHelper};
-use crate::helpers::pick_most_likely;
+#[cfg(feature = "hls")]
+use super::PlaylistHelper;
+use crate::helpers::{decode_mp3_from_chunk, pick_most_likely, AudioBuffer};
 use crossbeam_channel::{unbounded, Receiver, Sender};
+#[cfg(feature = "hls")]
 use m3u8_rs::playlist::{MasterPlaylist, MediaPlaylist, Playlist, VariantStream};
-use reqwest::{get, Url};
+#[cfg(feature = "hls")]
+use reqwest::get;
+use reqwest::Url;
 use std::collections::HashMap;
 use std::error::Error;
 use std::io::Cursor;
@@ -27,8 +31,8 @@ pub struct MatchesWatcher {
 #[derive(Clone, Debug)]
 struct StreamListener {
     uri: Url,
-    receiver: Receiver<Vec<f32>>,
-    sender: Sender<Vec<f32>>,
+    receiver: Receiver<AudioBuffer>,
+    sender: Sender<AudioBuffer>,
     is_active: bool,
 }
 
@@ -122,9 +126,9 @@ impl ArcStreamListener {
 
     /// Getter for receiver
     ///
-    /// # Returns receiver pipe that listen for decoded stream chunk
+    /// # Returns receiver pipe that listen for decoded stream chunk with its sample rate
     ///
-    pub fn get_listener(&self) -> Receiver<Vec<f32>> {
+    pub fn get_listener(&self) -> Receiver<AudioBuffer> {
         self.0.lock().unwrap().receiver.clone()
     }
 
@@ -146,6 +150,7 @@ impl ArcStreamListener {
     ///
     /// # Returns Ok if endpoint responded with valid playlist and stream or dyn Error otherwise
     ///
+    #[cfg(feature = "hls")]
     pub async fn run_m3u8(&mut self) -> Result<(), Box<dyn Error + 'static>> {
         let master_playlist = fetch_master_playlist(&self.0.lock().unwrap().uri).await?;
         if let Ok(uri) = master_playlist.find_uri() {
@@ -180,6 +185,7 @@ impl ArcStreamListener {
     }
 }
 
+#[cfg(feature = "hls")]
 impl PlaylistHelper for MasterPlaylist {
     fn find_uri(&self) -> Result<String, Box<dyn Error>> {
         let variants: Vec<VariantStream> = self
@@ -208,7 +214,7 @@ async fn listen_mp3_stream(listener: ArcStreamListener) -> Result<(), Box<dyn Er
         let decoded = decode_mp3_from_chunk(readable_buffer);
         match decoded {
             Ok(_result) => {
-                if _result.len() > 0 {
+                if !_result.samples.is_empty() {
                     listener.0.lock().unwrap().sender.send(_result)?;
                 }
             }
@@ -218,6 +224,7 @@ async fn listen_mp3_stream(listener: ArcStreamListener) -> Result<(), Box<dyn Er
     Ok(())
 }
 
+#[cfg(feature = "hls")]
 async fn fetch_master_playlist(uri: &Url) -> Result<MasterPlaylist, Box<dyn Error + 'static>> {
     let text = get_from_as_string(uri).await?;
     match m3u8_rs::parse_playlist_res(&text.as_bytes()) {
@@ -227,6 +234,7 @@ async fn fetch_master_playlist(uri: &Url) -> Result<MasterPlaylist, Box<dyn Erro
     }
 }
 
+#[cfg(feature = "hls")]
 async fn fetch_media_playlist(uri: &Url) -> Result<MediaPlaylist, Box<dyn Error + 'static>> {
     let text = get_from_as_string(uri).await?;
     match m3u8_rs::parse_playlist_res(&text.as_bytes()) {
@@ -238,6 +246,7 @@ async fn fetch_media_playlist(uri: &Url) -> Result<MediaPlaylist, Box<dyn Error
     }
 }
 
+#[cfg(feature = "hls")]
 async fn get_from_as_string(uri: &Url) -> Result<String, Box<dyn Error + 'static>> {
     let text = get(uri.clone()).await?.text().await?;
     Ok(text)
@@ -253,6 +262,7 @@ mod test {
 
     #[async_test]
     #[ignore]
+    #[cfg(feature = "hls")]
     async fn test_get_m3u8_stream() {
         dotenv::dotenv().ok();
         if dotenv!("RADIO_STREAM_ENABLED") == "true" {
@@ -281,10 +291,10 @@ mod test {
                     if !tested {
                         tested = true;
                         // testing is stream correct
-                        assert_eq!(decoded.len() > 0, true);
+                        assert_eq!(decoded.samples.len() > 0, true);
                         println!(
                             "\nReceived decoded stream of {:?} floats by crossbeam channel pipe",
-                            &decoded.len()
+                            &decoded.samples.len()
                         );
                     } else {
                         break;
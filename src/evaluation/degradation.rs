@@ -0,0 +1,304 @@
+use crate::helpers::{AudioBuffer, Biquad};
+use rand::prelude::*;
+use rustfft::algorithm::Radix4;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::FFT;
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt;
+
+const QUANTISATION_BLOCK: usize = 1024; // samples transformed at once by spectrum quantisation, blocks overlap by half
+const QUANTISATION_MAX_CUTOFF: f32 = 20000.0; // Hz, highest frequency kept by spectrum quantisation at any bitrate
+
+/// Distortion applied to a query before it is matched against the references
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Degradation {
+    /// Query is left as it is
+    Clean,
+    /// White noise added at signal to noise ratio in dB
+    WhiteNoise { snr: f32 },
+    /// Pink noise, with equal energy in every octave, added at signal to noise ratio in dB
+    PinkNoise { snr: f32 },
+    /// Spectrum quantisation resembling a transform codec at nominal bitrate in kbps
+    ///
+    /// Frequencies above the bitrate dependent cutoff are dropped
+    /// and spectrum is quantised more coarsely for lower bitrates.
+    /// It is not an MP3 encoder and its artifacts are not those of MP3 re-encoding,
+    /// queries re-encoded by a real encoder are evaluated by `Evaluation::run_queries`.
+    Quantisation { bitrate: usize },
+    /// Second order Butterworth low-pass filter with cutoff frequency in Hz
+    LowPass { cutoff: f32 },
+    /// Samples are clipped at the given fraction of the peak amplitude
+    Clipping { level: f32 },
+    /// Only the first seconds of the query are kept
+    Crop { duration: f32 },
+    /// Samples are amplified by the given number of dB, negative for attenuation
+    Gain { db: f32 },
+}
+
+impl Degradation {
+    /// Applies degradation to the query
+    ///
+    /// # Arguments:
+    /// * query - decoded query stream
+    /// * rng - seeded generator of noise, so evaluation is reproducible
+    ///
+    /// # Returns success of degraded query in the same sample rate, dynamic Error otherwise
+    ///
+    pub fn apply(
+        &self,
+        query: &AudioBuffer,
+        rng: &mut StdRng,
+    ) -> Result<AudioBuffer, Box<dyn Error>> {
+        let sample_rate = query.sample_rate;
+        if sample_rate == 0 {
+            return Err(Box::from("Invalid sample rate of query"));
+        }
+        let mut samples = query.samples.clone();
+        match self {
+            Degradation::Clean => {}
+            Degradation::WhiteNoise { snr } => {
+                let noise: Vec<f32> = (0..samples.len()).map(|_| white(rng)).collect();
+                add_noise(&mut samples, &noise, *snr);
+            }
+            Degradation::PinkNoise { snr } => {
+                // Paul Kellett's economy filter, pink within 0.5 dB above 1/500 of the sample rate
+                let (mut b0, mut b1, mut b2) = (0.0, 0.0, 0.0);
+                let noise: Vec<f32> = (0..samples.len())
+                    .map(|_| {
+                        let white = white(rng);
+                        b0 = 0.99765 * b0 + white * 0.0990460;
+                        b1 = 0.96300 * b1 + white * 0.2965164;
+                        b2 = 0.57000 * b2 + white * 1.0526913;
+                        b0 + b1 + b2 + white * 0.1848
+                    })
+                    .collect();
+                add_noise(&mut samples, &noise, *snr);
+            }
+            Degradation::Quantisation { bitrate } => {
+                if *bitrate == 0 {
+                    return Err(Box::from(
+                        "Bitrate of spectrum quantisation must be greater than zero",
+                    ));
+                }
+                quantise(&mut samples, sample_rate, *bitrate);
+            }
+            Degradation::LowPass { cutoff } => {
+                if !(*cutoff > 0.0 && *cutoff < sample_rate as f32 / 2.0) {
+                    return Err(Box::from(format!(
+                        "Low-pass cutoff must be between 0 and {} Hz, got {}",
+                        sample_rate / 2,
+                        cutoff
+                    )));
+                }
+                Biquad::low_pass(*cutoff, sample_rate).filter(&mut samples);
+            }
+            Degradation::Clipping { level } => {
+                if !(*level > 0.0 && *level <= 1.0) {
+                    return Err(Box::from(format!(
+                        "Clipping level must be between 0 and 1, got {}",
+                        level
+                    )));
+                }
+                let peak = samples
+                    .iter()
+                    .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+                let limit = peak * level;
+                samples
+                    .iter_mut()
+                    .for_each(|sample| *sample = sample.max(-limit).min(limit));
+            }
+            Degradation::Crop { duration } => {
+                let length = (duration.max(0.0) * sample_rate as f32) as usize;
+                samples.truncate(length);
+            }
+            Degradation::Gain { db } => {
+                let gain = 10_f32.powf(db / 20.0);
+                samples.iter_mut().for_each(|sample| *sample *= gain);
+            }
+        };
+        Ok(AudioBuffer::new(samples, sample_rate))
+    }
+}
+
+/// Short description used in evaluation reports
+///
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Degradation::Clean => write!(f, "clean"),
+            Degradation::WhiteNoise { snr } => write!(f, "white noise {} dB SNR", snr),
+            Degradation::PinkNoise { snr } => write!(f, "pink noise {} dB SNR", snr),
+            Degradation::Quantisation { bitrate } => write!(f, "quantisation {} kbps", bitrate),
+            Degradation::LowPass { cutoff } => write!(f, "low-pass {} Hz", cutoff),
+            Degradation::Clipping { level } => write!(f, "clipping at {} of peak", level),
+            Degradation::Crop { duration } => write!(f, "crop to {} s", duration),
+            Degradation::Gain { db } => write!(f, "gain {} dB", db),
+        }
+    }
+}
+
+/// Uniform white noise of unit variance
+///
+fn white(rng: &mut StdRng) -> f32 {
+    (rng.gen::<f32>() * 2.0 - 1.0) * 3_f32.sqrt()
+}
+
+/// Adds noise scaled to reach signal to noise ratio, silent signal is left as it is
+///
+fn add_noise(samples: &mut [f32], noise: &[f32], snr: f32) {
+    let power = |samples: &[f32]| {
+        samples
+            .iter()
+            .map(|sample| (*sample as f64).powi(2))
+            .sum::<f64>()
+            / samples.len().max(1) as f64
+    };
+    let (signal, noise_power) = (power(samples), power(noise));
+    if signal == 0.0 || noise_power == 0.0 {
+        return;
+    }
+    let gain = (signal / noise_power / 10_f64.powf(snr as f64 / 10.0)).sqrt() as f32;
+    for (sample, noise) in samples.iter_mut().zip(noise.iter()) {
+        *sample += noise * gain;
+    }
+}
+
+/// Quantises spectrum of the stream the way a transform codec does
+///
+/// Blocks of samples are windowed by sine window and transformed, bins above the cutoff are dropped
+/// and the rest is quantised with the step relative to the loudest bin of the block,
+/// then blocks are transformed back and overlapped, which reconstructs the stream
+/// exactly when nothing is dropped or quantised.
+///
+fn quantise(samples: &mut Vec<f32>, sample_rate: u32, bitrate: usize) {
+    // cutoff and resolution grow with bitrate, 128 kbps keeps about 17 kHz and 8 bits of spectrum
+    let cutoff = (3000.0 + 110.0 * bitrate as f32).min(QUANTISATION_MAX_CUTOFF);
+    let step_ratio = 2_f32.powi(-((bitrate / 16).max(1) as i32));
+    let hop = QUANTISATION_BLOCK / 2;
+    let window: Vec<f32> = (0..QUANTISATION_BLOCK)
+        .map(|n| (PI * (n as f32 + 0.5) / QUANTISATION_BLOCK as f32).sin())
+        .collect();
+    let forward = Radix4::new(QUANTISATION_BLOCK, false);
+    let inverse = Radix4::new(QUANTISATION_BLOCK, true);
+    let length = samples.len();
+    let mut padded = vec![0.0; hop];
+    padded.extend_from_slice(samples);
+    padded.resize(length + 3 * hop - length % hop, 0.0);
+    let mut output = vec![0.0; padded.len()];
+    let mut input = vec![Complex::zero(); QUANTISATION_BLOCK];
+    let mut spectrum = vec![Complex::zero(); QUANTISATION_BLOCK];
+    for start in (0..=padded.len() - QUANTISATION_BLOCK).step_by(hop) {
+        for (n, value) in input.iter_mut().enumerate() {
+            *value = Complex::from(padded[start + n] * window[n]);
+        }
+        forward.process(&mut input, &mut spectrum);
+        let step = spectrum
+            .iter()
+            .fold(0.0_f32, |peak, bin| peak.max(bin.norm()))
+            * step_ratio;
+        for (k, bin) in spectrum.iter_mut().enumerate() {
+            let frequency = k.min(QUANTISATION_BLOCK - k) as f32 * sample_rate as f32
+                / QUANTISATION_BLOCK as f32;
+            if frequency > cutoff || step == 0.0 {
+                *bin = Complex::zero();
+            } else {
+                *bin = Complex::new(
+                    (bin.re / step).round() * step,
+                    (bin.im / step).round() * step,
+                );
+            }
+        }
+        inverse.process(&mut spectrum, &mut input);
+        for (n, value) in input.iter().enumerate() {
+            output[start + n] += value.re / QUANTISATION_BLOCK as f32 * window[n];
+        }
+    }
+    samples.clear();
+    samples.extend_from_slice(&output[hop..hop + length]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Degradation;
+    use crate::helpers::AudioBuffer;
+    use rand::prelude::*;
+    use std::f32::consts::PI;
+
+    fn sine(freq: f32) -> AudioBuffer {
+        AudioBuffer::new(
+            (0..44100)
+                .map(|t| (2.0 * PI * freq * t as f32 / 44100.0).sin() * 10000.0)
+                .collect(),
+            44100,
+        )
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_degradations() {
+        let mut rng = StdRng::seed_from_u64(21);
+        let tone = sine(1000.0);
+        assert_eq!(Degradation::Clean.apply(&tone, &mut rng).unwrap(), tone);
+        for noise in [
+            Degradation::WhiteNoise { snr: 10.0 },
+            Degradation::PinkNoise { snr: 10.0 },
+        ]
+        .iter()
+        {
+            let noisy = noise.apply(&tone, &mut rng).unwrap();
+            let added: Vec<f32> = noisy
+                .samples
+                .iter()
+                .zip(tone.samples.iter())
+                .map(|(noisy, clean)| noisy - clean)
+                .collect();
+            let snr = 20.0 * (rms(&tone.samples) / rms(&added)).log10();
+            assert!((snr - 10.0).abs() < 0.01);
+        }
+        let gain = Degradation::Gain { db: -20.0 }
+            .apply(&tone, &mut rng)
+            .unwrap();
+        assert!((rms(&gain.samples) * 10.0 - rms(&tone.samples)).abs() < 1.0);
+        let crop = Degradation::Crop { duration: 0.5 }
+            .apply(&tone, &mut rng)
+            .unwrap();
+        assert_eq!(crop.samples.len(), 22050);
+        let clipped = Degradation::Clipping { level: 0.5 }
+            .apply(&tone, &mut rng)
+            .unwrap();
+        assert!(clipped.samples.iter().all(|sample| sample.abs() <= 5000.0));
+        let low_pass = Degradation::LowPass { cutoff: 2000.0 };
+        assert!(rms(&low_pass.apply(&sine(8000.0), &mut rng).unwrap().samples[4410..]) < 1000.0);
+        assert!(Degradation::LowPass { cutoff: 30000.0 }
+            .apply(&tone, &mut rng)
+            .is_err());
+        assert_eq!(
+            Degradation::Quantisation { bitrate: 64 }.to_string(),
+            "quantisation 64 kbps"
+        );
+    }
+    #[test]
+    fn test_quantisation() {
+        let mut rng = StdRng::seed_from_u64(22);
+        let quantisation = Degradation::Quantisation { bitrate: 64 };
+        let tone = sine(1000.0);
+        let quantised = quantisation.apply(&tone, &mut rng).unwrap();
+        assert_eq!(quantised.samples.len(), tone.samples.len());
+        let error: Vec<f32> = quantised
+            .samples
+            .iter()
+            .zip(tone.samples.iter())
+            .map(|(quantised, clean)| quantised - clean)
+            .collect();
+        assert!(rms(&error) < rms(&tone.samples) / 10.0);
+        // 64 kbps keeps frequencies up to about 10 kHz
+        let high = quantisation.apply(&sine(15000.0), &mut rng).unwrap();
+        assert!(rms(&high.samples) < 1.0);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::data::memory_actions::MemoryRepository;
+use crate::data::Repository;
+use crate::fingerprint::{FingerprintHandle, StreamingFingerprinter};
+use crate::helpers::AudioBuffer;
+use rand::prelude::*;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+mod degradation;
+pub use degradation::Degradation;
+
+const QUERY_DURATION: f32 = 10.0; // seconds of a reference each query is cut from
+const QUERIES_PER_REFERENCE: usize = 3; // queries cut from random positions of each reference
+const MIN_SCORE: usize = 8; // landmarks aligned at the best offset needed to report a match, random collisions rarely align more
+const IDENTIFY_STEP: f32 = 0.5; // seconds of a query streamed between attempts to identify it
+
+/// Recognition quality of queries distorted by a single degradation
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct DegradationReport {
+    /// Distortion applied to queries
+    pub degradation: Degradation,
+    /// Number of queries evaluated
+    pub queries: usize,
+    /// Share of queries identified as the reference they were cut from
+    pub recall: f32,
+    /// Share of reported matches that are the reference the query was cut from, 0 if nothing is reported
+    pub precision: f32,
+    /// Mean seconds of query audio streamed until it was identified, None if no query was identified
+    pub time_to_identify: Option<f32>,
+    /// Mean time of fingerprinting and matching a whole query
+    pub processing_time: Duration,
+}
+
+/// Evaluation result is printed as one line of a report
+///
+impl fmt::Display for DegradationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let time_to_identify = match self.time_to_identify {
+            Some(seconds) => format!("{:.1} s", seconds),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} queries, recall {:.2}, precision {:.2}, time to identify {}, processing {} ms",
+            self.degradation,
+            self.queries,
+            self.recall,
+            self.precision,
+            time_to_identify,
+            self.processing_time.as_millis()
+        )
+    }
+}
+
+/// Robustness evaluation of fingerprinting and matching
+///
+/// References are indexed in an in-process repository, then queries are cut from
+/// seeded random positions of each reference, distorted by each of degradations
+/// and matched against the index, the same queries for every degradation.
+/// Queries distorted outside of the crate, f.e. re-encoded to MP3, are matched
+/// by `run_queries` instead.
+/// Query is identified if the best match is its reference with at least minimum score.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    /// Distortions queries are evaluated with, each reported separately
+    pub degradations: Vec<Degradation>,
+    /// Seconds of a reference each query is cut from, whole reference if it is shorter
+    pub query_duration: f32,
+    /// Number of queries cut from each reference
+    pub queries_per_reference: usize,
+    /// Minimum number of aligned landmarks to report a match
+    pub min_score: usize,
+    /// Seed of query positions and noise, so evaluation is reproducible
+    pub seed: u64,
+}
+
+impl Default for Evaluation {
+    fn default() -> Self {
+        Self {
+            degradations: vec![
+                Degradation::Clean,
+                Degradation::WhiteNoise { snr: 10.0 },
+                Degradation::WhiteNoise { snr: 0.0 },
+                Degradation::PinkNoise { snr: 5.0 },
+                Degradation::Quantisation { bitrate: 64 },
+                Degradation::LowPass { cutoff: 2000.0 },
+                Degradation::Clipping { level: 0.3 },
+                Degradation::Crop { duration: 3.0 },
+                Degradation::Gain { db: -20.0 },
+            ],
+            query_duration: QUERY_DURATION,
+            queries_per_reference: QUERIES_PER_REFERENCE,
+            min_score: MIN_SCORE,
+            seed: 0,
+        }
+    }
+}
+
+impl Evaluation {
+    /// Runs evaluation
+    ///
+    /// # Arguments:
+    /// * handle - fingerprint handle references and queries are fingerprinted with
+    /// * references - author and title of each reference song together with its decoded stream
+    ///
+    /// # Returns success of report for each degradation in the same order, dynamic Error otherwise
+    ///
+    pub fn run(
+        &self,
+        handle: &FingerprintHandle,
+        references: &[(String, AudioBuffer)],
+    ) -> Result<Vec<DegradationReport>, Box<dyn Error>> {
+        let mut repository = index(handle, references)?;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut queries = Vec::new();
+        for (song, reference) in references.iter() {
+            let length = (self.query_duration * reference.sample_rate as f32) as usize;
+            let length = length.min(reference.samples.len());
+            for _ in 0..self.queries_per_reference {
+                let start = rng.gen_range(0, reference.samples.len() - length + 1);
+                let samples = reference.samples[start..start + length].to_vec();
+                let query = AudioBuffer::new(samples, reference.sample_rate);
+                queries.push((song.clone(), query));
+            }
+        }
+        self.degradations
+            .iter()
+            .map(|degradation| {
+                self.report(handle, &mut repository, degradation, &queries, &mut rng)
+            })
+            .collect()
+    }
+
+    /// Runs evaluation of queries distorted outside of the crate, f.e. re-encoded to MP3
+    ///
+    /// Queries are matched as they are given, so the report is made for `Clean` degradation.
+    ///
+    /// # Arguments:
+    /// * handle - fingerprint handle references and queries are fingerprinted with
+    /// * references - author and title of each reference song together with its decoded stream
+    /// * queries - author and title of the reference each query is made from together with
+    ///   decoded stream of the query
+    ///
+    /// # Returns success of report of all queries, dynamic Error otherwise,
+    /// also if a query is made from a song that is not among the references
+    ///
+    pub fn run_queries(
+        &self,
+        handle: &FingerprintHandle,
+        references: &[(String, AudioBuffer)],
+        queries: &[(String, AudioBuffer)],
+    ) -> Result<DegradationReport, Box<dyn Error>> {
+        if let Some((song, _)) = queries
+            .iter()
+            .find(|(song, _)| references.iter().all(|(reference, _)| reference != song))
+        {
+            return Err(Box::from(format!(
+                "Query is made from {}, which is not among the references",
+                song
+            )));
+        }
+        let mut repository = index(handle, references)?;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        self.report(
+            handle,
+            &mut repository,
+            &Degradation::Clean,
+            queries,
+            &mut rng,
+        )
+    }
+
+    /// Matches queries distorted by a single degradation against the indexed references
+    ///
+    /// # Returns success of report of the degradation, dynamic Error otherwise
+    ///
+    fn report(
+        &self,
+        handle: &FingerprintHandle,
+        repository: &mut MemoryRepository,
+        degradation: &Degradation,
+        queries: &[(String, AudioBuffer)],
+        rng: &mut StdRng,
+    ) -> Result<DegradationReport, Box<dyn Error>> {
+        let (mut answered, mut correct) = (0, 0);
+        let mut identified = Vec::new();
+        let mut processing_time = Duration::default();
+        for (song, query) in queries.iter() {
+            let query = degradation.apply(query, rng)?;
+            let start = Instant::now();
+            let fingerprints = handle.calc_fingerprint_collection(&query)?;
+            let matches = repository.locate_matches(handle.config(), &fingerprints)?;
+            processing_time += start.elapsed();
+            if let Some(best) = matches.first().filter(|best| best.score >= self.min_score) {
+                answered += 1;
+                if best.song == *song {
+                    correct += 1;
+                }
+            }
+            if let Some(seconds) = self.time_to_identify(handle, repository, &query, song)? {
+                identified.push(seconds);
+            }
+        }
+        Ok(DegradationReport {
+            degradation: degradation.clone(),
+            queries: queries.len(),
+            recall: correct as f32 / queries.len().max(1) as f32,
+            precision: correct as f32 / (answered as f32).max(1.0),
+            time_to_identify: match identified.len() {
+                0 => None,
+                count => Some(identified.iter().sum::<f32>() / count as f32),
+            },
+            processing_time: processing_time / queries.len().max(1) as u32,
+        })
+    }
+
+    /// Streams the query in short steps until the best match is the expected song
+    ///
+    /// Preprocessing normalising loudness can not be streamed,
+    /// then the whole query streamed so far is fingerprinted at each step instead.
+    ///
+    /// # Returns success of Option with seconds of query streamed until identified,
+    /// None if the query is not identified at all, dynamic Error otherwise
+    ///
+    fn time_to_identify(
+        &self,
+        handle: &FingerprintHandle,
+        repository: &mut MemoryRepository,
+        query: &AudioBuffer,
+        song: &str,
+    ) -> Result<Option<f32>, Box<dyn Error>> {
+        let config = handle.config();
+        let step = ((IDENTIFY_STEP * query.sample_rate as f32) as usize).max(1);
+        let streamable = match &config.preprocessor {
+            Some(preprocessor) => preprocessor.target_level.is_none(),
+            None => true,
+        };
+        let mut fingerprinter = if streamable {
+            Some(StreamingFingerprinter::with_sample_rate(
+                config.clone(),
+                query.sample_rate,
+            )?)
+        } else {
+            None
+        };
+        let mut fingerprints = Vec::new();
+        for (idx, chunk) in query.samples.chunks(step).enumerate() {
+            let streamed = idx * step + chunk.len();
+            match fingerprinter.as_mut() {
+                Some(fingerprinter) => fingerprints.extend(fingerprinter.push(chunk)),
+                None => {
+                    fingerprints = handle.calc_fingerprint_collection(&AudioBuffer::new(
+                        query.samples[..streamed].to_vec(),
+                        query.sample_rate,
+                    ))?
+                }
+            }
+            let matches = repository.locate_matches(config, &fingerprints)?;
+            if let Some(best) = matches.first() {
+                if best.score >= self.min_score && best.song == song {
+                    return Ok(Some(streamed as f32 / query.sample_rate as f32));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Indexes references in an in-process repository
+///
+/// # Returns success of repository with all references stored, dynamic Error otherwise
+///
+fn index(
+    handle: &FingerprintHandle,
+    references: &[(String, AudioBuffer)],
+) -> Result<MemoryRepository, Box<dyn Error>> {
+    let mut repository = MemoryRepository::new();
+    for (song, reference) in references.iter() {
+        let fingerprints = handle.calc_fingerprint_collection(reference)?;
+        repository.store(handle.config(), &fingerprints, song)?;
+    }
+    Ok(repository)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Degradation, Evaluation};
+    use crate::fingerprint::{FingerprintConfig, FingerprintHandle};
+    use crate::helpers::{AudioBuffer, Preprocessor, SignalGenerator};
+
+    #[test]
+    fn test_evaluation() {
+        let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+        let references: Vec<(String, AudioBuffer)> = (0..3)
+            .map(|seed| {
+                let song = SignalGenerator::new(seed, 11025).song(15.0, 0.5);
+                (format!("song {}", seed), song)
+            })
+            .collect();
+        let evaluation = Evaluation {
+            degradations: vec![
+                Degradation::Clean,
+                Degradation::WhiteNoise { snr: 0.0 },
+                Degradation::Crop { duration: 1.0 },
+            ],
+            query_duration: 5.0,
+            queries_per_reference: 2,
+            ..Evaluation::default()
+        };
+        let reports = evaluation.run(&handle, &references).unwrap();
+        assert_eq!(reports.len(), 3);
+        for report in reports.iter() {
+            assert_eq!(report.queries, 6);
+            assert!(report.precision >= report.recall);
+        }
+        let clean = &reports[0];
+        assert_eq!(clean.degradation, Degradation::Clean);
+        assert_eq!(clean.recall, 1.0);
+        assert_eq!(clean.precision, 1.0);
+        assert!(clean.time_to_identify.unwrap() <= 5.0);
+        assert!(clean
+            .to_string()
+            .starts_with("clean: 6 queries, recall 1.00"));
+        // cropped queries are identified within their duration, if at all
+        assert!(reports[2].time_to_identify.unwrap_or(0.0) <= 1.0);
+    }
+    #[test]
+    fn test_evaluation_with_loudness_normalisation() {
+        let handle = FingerprintHandle::new(FingerprintConfig {
+            preprocessor: Some(Preprocessor::default()),
+            ..FingerprintConfig::default()
+        })
+        .unwrap();
+        let references: Vec<(String, AudioBuffer)> = (0..2)
+            .map(|seed| {
+                let song = SignalGenerator::new(seed, 11025).song(10.0, 0.5);
+                (format!("song {}", seed), song)
+            })
+            .collect();
+        let evaluation = Evaluation {
+            degradations: vec![Degradation::Clean],
+            query_duration: 3.0,
+            queries_per_reference: 1,
+            ..Evaluation::default()
+        };
+        let reports = evaluation.run(&handle, &references).unwrap();
+        assert_eq!(reports[0].recall, 1.0);
+        assert!(reports[0].time_to_identify.unwrap() <= 3.0);
+    }
+    #[test]
+    fn test_evaluation_of_supplied_queries() {
+        let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+        let references: Vec<(String, AudioBuffer)> = (0..2)
+            .map(|seed| {
+                let song = SignalGenerator::new(seed, 11025).song(10.0, 0.5);
+                (format!("song {}", seed), song)
+            })
+            .collect();
+        // queries distorted outside of the evaluation, cut from the middle and attenuated
+        let queries: Vec<(String, AudioBuffer)> = references
+            .iter()
+            .map(|(song, reference)| {
+                let samples = reference.samples[11025 * 3..11025 * 7]
+                    .iter()
+                    .map(|sample| sample * 0.3)
+                    .collect();
+                (
+                    song.clone(),
+                    AudioBuffer::new(samples, reference.sample_rate),
+                )
+            })
+            .collect();
+        let evaluation = Evaluation::default();
+        let report = evaluation
+            .run_queries(&handle, &references, &queries)
+            .unwrap();
+        assert_eq!(report.degradation, Degradation::Clean);
+        assert_eq!(report.queries, 2);
+        assert_eq!(report.recall, 1.0);
+        assert_eq!(report.precision, 1.0);
+
+        let unknown = vec![("song 7".to_string(), queries[0].1.clone())];
+        assert!(evaluation
+            .run_queries(&handle, &references, &unknown)
+            .is_err());
+    }
+}
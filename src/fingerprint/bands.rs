@@ -0,0 +1,247 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Layout of frequency bands a peak is searched for in
+///
+/// Bands are described by their edges, each edge is the top frequency of a band
+/// and the lowest edge is the lowest analysed frequency.
+/// Layouts defined in Hz are mapped to frequency bins of the configured sample rate
+/// and window size, so the same bands are analysed regardless of the configuration.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum BandLayout {
+    /// Edges given directly as frequency bins, valid for a single sample rate and window size
+    Bins(Vec<usize>),
+    /// Bands of equal width in Hz
+    Linear {
+        /// Lowest analysed frequency in Hz
+        low: f32,
+        /// Highest analysed frequency in Hz
+        high: f32,
+        /// Number of bands between the lowest and the highest frequency
+        bands: usize,
+    },
+    /// Bands of equal width in octaves, so high frequencies are not covered by most of the bands
+    Logarithmic {
+        /// Lowest analysed frequency in Hz, must be greater than zero
+        low: f32,
+        /// Highest analysed frequency in Hz
+        high: f32,
+        /// Number of bands between the lowest and the highest frequency
+        bands: usize,
+    },
+    /// Bands of equal width on mel scale, following pitch perception
+    Mel {
+        /// Lowest analysed frequency in Hz
+        low: f32,
+        /// Highest analysed frequency in Hz
+        high: f32,
+        /// Number of bands between the lowest and the highest frequency
+        bands: usize,
+    },
+}
+
+impl BandLayout {
+    /// Calculates band edges as frequency bins
+    ///
+    /// # Arguments:
+    /// * sample_rate - sample rate of fingerprinted stream
+    /// * window_size - number of samples processed by fast fourier transform in one frame
+    ///
+    /// # Returns collection of frequency bins, the lowest edge first,
+    /// edges closer than a single bin may collapse in to the same bin
+    ///
+    pub fn edges(&self, sample_rate: u32, window_size: usize) -> Vec<usize> {
+        let frequencies: Vec<f32> = match self {
+            BandLayout::Bins(edges) => return edges.clone(),
+            BandLayout::Linear { low, high, bands } => (0..=*bands)
+                .map(|band| low + (high - low) * band as f32 / *bands as f32)
+                .collect(),
+            BandLayout::Logarithmic { low, high, bands } => (0..=*bands)
+                .map(|band| low * (high / low).powf(band as f32 / *bands as f32))
+                .collect(),
+            BandLayout::Mel { low, high, bands } => {
+                let (low, high) = (hz_to_mel(*low), hz_to_mel(*high));
+                (0..=*bands)
+                    .map(|band| mel_to_hz(low + (high - low) * band as f32 / *bands as f32))
+                    .collect()
+            }
+        };
+        frequencies
+            .iter()
+            .map(|frequency| (frequency * window_size as f32 / sample_rate as f32).round() as usize)
+            .collect()
+    }
+
+    /// Checks if layout parameters describe any bands
+    ///
+    /// # Returns success if layout can be mapped to frequency bins, dynamic Error otherwise
+    ///
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let (low, high, bands) = match self {
+            BandLayout::Bins(_) => return Ok(()),
+            BandLayout::Linear { low, high, bands } | BandLayout::Mel { low, high, bands } => {
+                (*low, *high, *bands)
+            }
+            BandLayout::Logarithmic { low, high, bands } => {
+                if *low <= 0.0 {
+                    return Err(Box::from(format!(
+                        "Lowest frequency of logarithmic bands must be greater than zero, got {}",
+                        low
+                    )));
+                }
+                (*low, *high, *bands)
+            }
+        };
+        if !(low >= 0.0 && low < high && high.is_finite()) {
+            return Err(Box::from(format!(
+                "Band frequencies must be increasing from a non negative one, got {} and {}",
+                low, high
+            )));
+        }
+        if bands == 0 {
+            return Err(Box::from("Number of bands must be greater than zero"));
+        }
+        Ok(())
+    }
+}
+
+/// Band layout string is a part of configuration string stored in the repository
+///
+impl fmt::Display for BandLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, low, high, bands) = match self {
+            BandLayout::Bins(edges) => {
+                let edges: Vec<String> = edges.iter().map(|e| e.to_string()).collect();
+                return write!(f, "{}", edges.join(","));
+            }
+            BandLayout::Linear { low, high, bands } => ("linear", low, high, bands),
+            BandLayout::Logarithmic { low, high, bands } => ("log", low, high, bands),
+            BandLayout::Mel { low, high, bands } => ("mel", low, high, bands),
+        };
+        write!(f, "{}:{},{},{}", name, low, high, bands)
+    }
+}
+
+impl FromStr for BandLayout {
+    type Err = Box<dyn Error>;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.splitn(2, ':');
+        let (name, values) = match (parts.next(), parts.next()) {
+            (Some(name), Some(values)) => (name, values),
+            _ => {
+                let edges: Result<Vec<usize>, _> = text.split(',').map(str::parse).collect();
+                return Ok(BandLayout::Bins(edges?));
+            }
+        };
+        let values: Vec<&str> = values.split(',').collect();
+        if values.len() != 3 {
+            return Err(Box::from(format!("Invalid band layout: {}", text)));
+        }
+        let (low, high, bands) = (values[0].parse()?, values[1].parse()?, values[2].parse()?);
+        match name {
+            "linear" => Ok(BandLayout::Linear { low, high, bands }),
+            "log" => Ok(BandLayout::Logarithmic { low, high, bands }),
+            "mel" => Ok(BandLayout::Mel { low, high, bands }),
+            _ => Err(Box::from(format!("Unknown band layout: {}", name))),
+        }
+    }
+}
+
+fn hz_to_mel(frequency: f32) -> f32 {
+    2595.0 * (1.0 + frequency / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10_f32.powf(mel / 2595.0) - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BandLayout;
+
+    #[test]
+    fn test_edges() {
+        let bins = BandLayout::Bins(vec![32, 40, 80]);
+        assert_eq!(bins.edges(11025, 1024), vec![32, 40, 80]);
+        assert_eq!(bins.edges(44100, 4096), vec![32, 40, 80]);
+
+        let linear = BandLayout::Linear {
+            low: 0.0,
+            high: 4000.0,
+            bands: 4,
+        };
+        assert_eq!(linear.edges(8000, 1024), vec![0, 128, 256, 384, 512]);
+
+        let log = BandLayout::Logarithmic {
+            low: 250.0,
+            high: 4000.0,
+            bands: 4,
+        };
+        assert_eq!(log.edges(8000, 1024), vec![32, 64, 128, 256, 512]);
+        // the same frequencies for a different configuration
+        assert_eq!(log.edges(16000, 4096), vec![64, 128, 256, 512, 1024]);
+
+        let mel = BandLayout::Mel {
+            low: 300.0,
+            high: 5000.0,
+            bands: 6,
+        };
+        let edges = mel.edges(11025, 1024);
+        assert_eq!(edges.len(), 7);
+        assert_eq!(edges[0], 28);
+        assert_eq!(edges[6], 464);
+        let widths: Vec<usize> = edges.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(widths.windows(2).all(|w| w[0] < w[1]));
+    }
+    #[test]
+    fn test_layout_string() {
+        let layouts = [
+            BandLayout::Bins(vec![32, 40, 80]),
+            BandLayout::Linear {
+                low: 0.0,
+                high: 4000.5,
+                bands: 4,
+            },
+            BandLayout::Logarithmic {
+                low: 250.0,
+                high: 4000.0,
+                bands: 4,
+            },
+            BandLayout::Mel {
+                low: 300.0,
+                high: 5000.0,
+                bands: 6,
+            },
+        ];
+        for layout in layouts.iter() {
+            assert!(layout.validate().is_ok());
+            assert_eq!(layout.to_string().parse::<BandLayout>().unwrap(), *layout);
+        }
+        assert_eq!(layouts[3].to_string(), "mel:300,5000,6");
+        assert!("bark:300,5000,6".parse::<BandLayout>().is_err());
+        assert!("mel:300,5000".parse::<BandLayout>().is_err());
+        let invalid = [
+            BandLayout::Logarithmic {
+                low: 0.0,
+                high: 4000.0,
+                bands: 4,
+            },
+            BandLayout::Mel {
+                low: 5000.0,
+                high: 300.0,
+                bands: 6,
+            },
+            BandLayout::Linear {
+                low: 0.0,
+                high: 4000.0,
+                bands: 0,
+            },
+        ];
+        for layout in invalid.iter() {
+            assert!(layout.validate().is_err());
+        }
+    }
+}
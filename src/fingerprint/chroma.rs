@@ -0,0 +1,254 @@
+use super::Spectrogram;
+
+const PITCH_CLASSES: usize = 12; // semitones in an octave
+const REFERENCE_PITCH: f32 = 130.81; // C3 in Hz, pitch class 0
+const HIGHEST_PITCH: f32 = 2093.0; // C7 in Hz, higher bins hold mostly harmonics and noise
+const FRAMES_PER_VECTOR: usize = 10; // spectrogram frames summed in to a single chroma vector, about half a second for default configuration
+
+/// Energy of each pitch class of a single time step
+pub type Chroma = [f32; PITCH_CLASSES];
+
+/// Sequence of pitch class profiles of a track
+///
+/// Spectrum energy is folded in to 12 pitch classes regardless of octave,
+/// so the profile follows harmony and melody rather than timbre of the instruments.
+/// Spectrogram frames are summed in to longer time steps and each step is normalised,
+/// so the sequence does not depend on loudness and is short enough for sequence alignment.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chromagram {
+    /// Chroma vectors of unit length, or zero vectors for silent time steps
+    pub vectors: Vec<Chroma>,
+    /// Time in seconds covered by a single chroma vector
+    pub vector_duration: f32,
+}
+
+/// Musical similarity of two tracks
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChromaSimilarity {
+    /// Mean cosine similarity of chroma vectors along the alignment path, from 0 to 1
+    pub score: f32,
+    /// Number of semitones the second track is transposed up from the first one, from 0 to 11
+    pub transposition: usize,
+    /// Number of aligned pairs of chroma vectors
+    pub path_length: usize,
+}
+
+impl Chromagram {
+    /// Create new instance of Chromagram
+    ///
+    /// # Arguments:
+    /// * spectrogram - magnitudes of the track, f.e. calculated by `FingerprintHandle::calc_spectrogram`
+    ///
+    /// # Returns new instance of Chromagram
+    ///
+    pub fn new(spectrogram: &Spectrogram) -> Self {
+        let bin_width = spectrogram.sample_rate as f32 / spectrogram.window_size as f32;
+        // below the lowest pitch neighbouring semitones are closer to each other than a frequency bin
+        let lowest_pitch = bin_width / (2_f32.powf(1.0 / PITCH_CLASSES as f32) - 1.0);
+        let classes: Vec<Option<usize>> = (0..spectrogram.window_size / 2)
+            .map(|bin| {
+                let frequency = bin as f32 * bin_width;
+                if !(lowest_pitch..=HIGHEST_PITCH).contains(&frequency) {
+                    return None;
+                }
+                let semitone = (12.0 * (frequency / REFERENCE_PITCH).log2()).round() as isize;
+                Some(semitone.rem_euclid(PITCH_CLASSES as isize) as usize)
+            })
+            .collect();
+        let vectors = spectrogram
+            .magnitudes
+            .chunks(FRAMES_PER_VECTOR)
+            .map(|frames| {
+                let mut chroma = [0.0; PITCH_CLASSES];
+                for magnitudes in frames.iter() {
+                    for (magnitude, class) in magnitudes.iter().zip(classes.iter()) {
+                        if let Some(class) = class {
+                            chroma[*class] += magnitude * magnitude;
+                        }
+                    }
+                }
+                normalise(&mut chroma);
+                chroma
+            })
+            .collect();
+        Self {
+            vectors,
+            vector_duration: (FRAMES_PER_VECTOR * spectrogram.hop_size) as f32
+                / spectrogram.sample_rate as f32,
+        }
+    }
+
+    /// Compares musical content of two tracks, f.e. a song and its cover or live version
+    ///
+    /// Transposition of the second track is estimated from mean chroma of both tracks first,
+    /// then both sequences are aligned by dynamic time warping, which allows
+    /// the tracks to be played at a different or varying tempo.
+    ///
+    /// # Arguments:
+    /// * other - chromagram of the second track, calculated with the same configuration
+    ///
+    /// # Returns similarity of the tracks, default similarity if any of them is empty
+    ///
+    pub fn compare(&self, other: &Chromagram) -> ChromaSimilarity {
+        if self.vectors.is_empty() || other.vectors.is_empty() {
+            return ChromaSimilarity::default();
+        }
+        let (mean, other_mean) = (self.mean(), other.mean());
+        let transposition = (0..PITCH_CLASSES)
+            .max_by(|a, b| {
+                let a = similarity(&mean, &other_mean, *a);
+                let b = similarity(&mean, &other_mean, *b);
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(0);
+        // accumulated distance together with number of steps of the best path, one row at a time
+        let mut previous: Vec<(f32, usize)> = Vec::with_capacity(other.vectors.len());
+        let mut current: Vec<(f32, usize)> = Vec::with_capacity(other.vectors.len());
+        for (row, chroma) in self.vectors.iter().enumerate() {
+            current.clear();
+            for (column, other_chroma) in other.vectors.iter().enumerate() {
+                let distance = 1.0 - similarity(chroma, other_chroma, transposition);
+                let best = match (row, column) {
+                    (0, 0) => (0.0, 0),
+                    (0, _) => current[column - 1],
+                    (_, 0) => previous[column],
+                    _ => [current[column - 1], previous[column], previous[column - 1]]
+                        .iter()
+                        .cloned()
+                        .fold((f32::INFINITY, 0), |best, step| {
+                            if step.0 < best.0 {
+                                step
+                            } else {
+                                best
+                            }
+                        }),
+                };
+                current.push((best.0 + distance, best.1 + 1));
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        let (distance, path_length) = previous[other.vectors.len() - 1];
+        ChromaSimilarity {
+            score: (1.0 - distance / path_length as f32).max(0.0),
+            transposition,
+            path_length,
+        }
+    }
+
+    /// Mean chroma of the whole track, normalised to unit length
+    ///
+    fn mean(&self) -> Chroma {
+        let mut mean = [0.0; PITCH_CLASSES];
+        for chroma in self.vectors.iter() {
+            for (sum, value) in mean.iter_mut().zip(chroma.iter()) {
+                *sum += value;
+            }
+        }
+        normalise(&mut mean);
+        mean
+    }
+}
+
+/// Scales chroma to unit length, leaves silent chroma as zero vector
+///
+fn normalise(chroma: &mut Chroma) {
+    let norm = chroma.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        chroma.iter_mut().for_each(|value| *value /= norm);
+    }
+}
+
+/// Cosine similarity of chroma vectors of unit length, with pitch classes of the second one
+/// shifted down by transposition, two silent vectors are equal
+///
+fn similarity(chroma: &Chroma, other: &Chroma, transposition: usize) -> f32 {
+    let silent = |chroma: &Chroma| chroma.iter().all(|value| *value == 0.0);
+    if silent(chroma) && silent(other) {
+        return 1.0;
+    }
+    (0..PITCH_CLASSES)
+        .map(|class| chroma[class] * other[(class + transposition) % PITCH_CLASSES])
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{FingerprintConfig, FingerprintHandle};
+    use crate::helpers::AudioBuffer;
+    use rand::prelude::*;
+    use std::f32::consts::PI;
+
+    /// Progression of triads, each given as semitones above C3
+    ///
+    fn progression(seed: u64, chords: usize) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..chords).map(|_| rng.gen_range(0, 12)).collect()
+    }
+
+    /// Plays triads of the progression, harmonics set the timbre of the instrument
+    ///
+    fn play(
+        roots: &[usize],
+        transposition: usize,
+        chord_length: f32,
+        harmonics: &[f32],
+    ) -> AudioBuffer {
+        let sample_rate = 11025.0;
+        let length = (chord_length * sample_rate) as usize;
+        let mut samples = Vec::new();
+        for root in roots.iter() {
+            let notes: Vec<f32> = [0, 4, 7]
+                .iter()
+                .map(|interval| {
+                    // an octave above the reference, so every note lies above the lowest pitch
+                    2.0 * super::REFERENCE_PITCH
+                        * 2_f32.powf((root + transposition + interval) as f32 / 12.0)
+                })
+                .collect();
+            samples.extend((0..length).map(|t| {
+                let time = t as f32 / sample_rate;
+                let mut sample = 0.0;
+                for note in notes.iter() {
+                    for (harmonic, amplitude) in harmonics.iter().enumerate() {
+                        sample +=
+                            (2.0 * PI * note * (harmonic + 1) as f32 * time).sin() * amplitude;
+                    }
+                }
+                sample * 3000.0
+            }));
+        }
+        AudioBuffer::new(samples, 11025)
+    }
+
+    #[test]
+    fn test_cover_similarity() {
+        let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+        let song = progression(1, 16);
+        let original = handle
+            .calc_chromagram(&play(&song, 0, 1.0, &[1.0]))
+            .unwrap();
+        // slower cover two semitones higher, played by an instrument rich in harmonics
+        let cover = handle
+            .calc_chromagram(&play(&song, 2, 1.25, &[1.0, 0.5, 0.3]))
+            .unwrap();
+        let other = handle
+            .calc_chromagram(&play(&progression(2, 16), 0, 1.0, &[1.0]))
+            .unwrap();
+
+        let itself = original.compare(&original);
+        assert!((itself.score - 1.0).abs() < 1e-3);
+        assert_eq!(itself.transposition, 0);
+        let covered = original.compare(&cover);
+        assert_eq!(covered.transposition, 2);
+        assert!(covered.path_length >= cover.vectors.len());
+        let unrelated = original.compare(&other);
+        assert!(covered.score > 0.8);
+        assert!(covered.score > unrelated.score + 0.1);
+        assert_eq!(
+            original.compare(&super::Chromagram::default()),
+            super::ChromaSimilarity::default()
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use super::{Hash, Landmark};
+use crate::helpers::OffsetVotes;
+use std::collections::HashMap;
+
+/// Similarity of two fingerprinted clips, calculated without a repository
+///
+/// Landmarks of both clips are aligned the same way a repository aligns a sample with a song,
+/// by voting for time offset between landmarks with equal hashes.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Similarity {
+    /// Share of landmarks of the shorter clip aligned at the best offset, from 0 to 1
+    pub score: f32,
+    /// Number of landmarks aligned at the best offset
+    pub matched: usize,
+    /// Time in seconds the second clip starts at in the first clip, negative if it starts earlier
+    pub offset: f32,
+    /// Time in seconds between the first and the last aligned landmark of the second clip
+    pub matched_duration: f32,
+}
+
+impl Similarity {
+    /// Compares landmarks of two clips
+    ///
+    /// # Arguments:
+    /// * first - landmarks of the first clip
+    /// * second - landmarks of the second clip, calculated with the same configuration
+    /// * frame_duration - time in seconds between starts of consecutive frames
+    ///
+    /// # Returns similarity of the clips, default similarity if no hashes are equal
+    ///
+    pub fn new(first: &[Landmark], second: &[Landmark], frame_duration: f32) -> Self {
+        let mut index: HashMap<Hash, Vec<usize>> = HashMap::new();
+        for landmark in first.iter() {
+            index
+                .entry(landmark.hash)
+                .or_default()
+                .push(landmark.offset);
+        }
+        // both clips are voted for as a single song, so the first clip takes place of the song
+        let mut votes = OffsetVotes::new();
+        for landmark in second.iter() {
+            if let Some(offsets) = index.get(&landmark.hash) {
+                for offset in offsets.iter() {
+                    votes.vote("", *offset, landmark.offset);
+                }
+            }
+        }
+        match votes.best_alignment("") {
+            Some((delta, aligned)) => {
+                let shorter = first.len().min(second.len());
+                Self {
+                    score: (aligned.votes as f32 / shorter as f32).min(1.0),
+                    matched: aligned.votes,
+                    offset: delta as f32 * frame_duration,
+                    matched_duration: (aligned.last - aligned.first) as f32 * frame_duration,
+                }
+            }
+            None => Self::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{FingerprintConfig, FingerprintHandle};
+    use crate::helpers::{AudioBuffer, SignalGenerator};
+
+    fn tones(seed: u64, tones: usize) -> Vec<f32> {
+        SignalGenerator::new(seed, 11025)
+            .tone_sequence(tones, 0.1, 0.3)
+            .samples
+    }
+
+    #[test]
+    fn test_compare() {
+        let config = FingerprintConfig::default();
+        let handle = FingerprintHandle::new(config.clone()).unwrap();
+        let song = tones(1, 100);
+        let full = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(song.clone(), 11025))
+            .unwrap();
+        let start = 200 * config.hop_size;
+        let excerpt = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(song[start..].to_vec(), 11025))
+            .unwrap();
+
+        let itself = handle.compare(&full, &full);
+        assert_eq!(itself.score, 1.0);
+        assert_eq!(itself.offset, 0.0);
+        assert!(itself.matched_duration > 9.0);
+
+        let aligned = handle.compare(&full, &excerpt);
+        let expected = start as f32 / config.sample_rate as f32;
+        assert!((aligned.offset - expected).abs() < 1e-3);
+        assert!(aligned.score > 0.5);
+        assert_eq!(handle.compare(&excerpt, &full).offset, -aligned.offset);
+
+        let other = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(tones(2, 100), 11025))
+            .unwrap();
+        assert!(handle.compare(&full, &other).score < 0.1);
+        assert_eq!(handle.compare(&full, &[]), super::Similarity::default());
+    }
+}
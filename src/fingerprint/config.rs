@@ -0,0 +1,375 @@
+use super::hash::MAX_BIN;
+use super::{BandLayout, WindowFunction};
+use crate::helpers::Preprocessor;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+const SAMPLE_RATE: u32 = 11025; // every stream is resampled to this rate, so frequency bins mean the same Hz for every source
+const FFT_WINDOW_SIZE: usize = 1024; // chunk window size to process by fast forward fourier function
+const HOP_SIZE: usize = FFT_WINDOW_SIZE / 2; // frames overlap by half of the window, so sample start is never far from frame start
+const FREQ_BINS: &[usize] = &[32, 40, 80, 120, 180, 320]; // Each value in array is a top range frequency to calculate local maximum magnitude for
+const FUZZ_FACTOR: usize = 2; // higher the value of this factor, lower the fingerprint entropy, and less bias the algorithm become to the sound noises
+const SILENCE_THRESHOLD: f32 = 32.0; // root mean square of 16 bit frame samples, about -60 dBFS, below which the frame is silent
+const FLATNESS_THRESHOLD: f32 = 0.8; // spectral flatness above which the frame has no tonal structure, white noise scores about 0.56
+const PEAK_MARGIN: f32 = 10.0; // dB over the noise floor a peak has to reach, loudest bin of a wide noise band rarely exceeds its median by that much
+const MAX_PEAKS_PER_SECOND: usize = 64; // about half of one peak per band in each of default frames
+
+/// Parameters of fingerprint calculation
+///
+/// Songs indexed with one configuration can be matched only by samples
+/// fingerprinted with exactly the same configuration, window function included,
+/// that is why configuration is stored in the repository together with the fingerprints.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct FingerprintConfig {
+    /// Sample rate streams are resampled to before calculating fingerprints
+    pub sample_rate: u32,
+    /// Number of samples processed by fast fourier transform in one frame, must be power of two
+    pub window_size: usize,
+    /// Number of samples between starts of consecutive frames
+    pub hop_size: usize,
+    /// Layout of frequency bands to find local maximum magnitude in
+    pub bands: BandLayout,
+    /// Frequency bins are rounded down to a multiple of this factor
+    pub fuzz_factor: usize,
+    /// Window function applied to frame samples
+    pub window: WindowFunction,
+    /// Frames with root mean square of samples below this level are skipped as silent
+    pub silence_threshold: f32,
+    /// Frames with spectral flatness of the banded range above this level are skipped as noise,
+    /// flatness is between 0 for a pure tone and 1 for a perfectly flat spectrum
+    pub flatness_threshold: f32,
+    /// Number of dB a peak has to exceed local noise floor by to be accepted
+    pub peak_margin: f32,
+    /// Max number of peaks accepted in one second of the stream
+    pub max_peaks_per_second: usize,
+    /// Preprocessing chain applied to resampled stream, None keeps samples as decoded
+    pub preprocessor: Option<Preprocessor>,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: SAMPLE_RATE,
+            window_size: FFT_WINDOW_SIZE,
+            hop_size: HOP_SIZE,
+            bands: BandLayout::Bins(FREQ_BINS.to_vec()),
+            fuzz_factor: FUZZ_FACTOR,
+            window: WindowFunction::Hann,
+            silence_threshold: SILENCE_THRESHOLD,
+            flatness_threshold: FLATNESS_THRESHOLD,
+            peak_margin: PEAK_MARGIN,
+            max_peaks_per_second: MAX_PEAKS_PER_SECOND,
+            preprocessor: None,
+        }
+    }
+}
+
+impl FingerprintConfig {
+    /// Checks if parameters are consistent with each other
+    ///
+    /// # Returns success if configuration can be used for calculating fingerprints, dynamic Error otherwise
+    ///
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.sample_rate == 0 {
+            return Err(Box::from("Sample rate must be greater than zero"));
+        }
+        if !self.window_size.is_power_of_two() {
+            return Err(Box::from(format!(
+                "Window size must be power of two, got {}",
+                self.window_size
+            )));
+        }
+        if self.hop_size == 0 || self.hop_size > self.window_size {
+            return Err(Box::from(format!(
+                "Hop size must be positive and not greater than window size {}, got {}",
+                self.window_size, self.hop_size
+            )));
+        }
+        self.bands.validate()?;
+        let band_edges = self.band_edges();
+        if band_edges.len() < 2 {
+            return Err(Box::from("At least two band edges are required"));
+        }
+        if band_edges[0] == 0 || band_edges.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Box::from(format!(
+                "Band edges must be positive and strictly increasing frequency bins, got {:?}",
+                band_edges
+            )));
+        }
+        let top_edge = band_edges[band_edges.len() - 1];
+        if top_edge >= self.window_size / 2 || top_edge >= MAX_BIN {
+            return Err(Box::from(format!(
+                "Top band edge {} must be lower than half of window size {} and lower than {}",
+                top_edge, self.window_size, MAX_BIN
+            )));
+        }
+        if self.fuzz_factor == 0 {
+            return Err(Box::from("Fuzz factor must be greater than zero"));
+        }
+        if !(self.silence_threshold >= 0.0 && self.silence_threshold.is_finite()) {
+            return Err(Box::from(format!(
+                "Silence threshold must be finite and not negative, got {}",
+                self.silence_threshold
+            )));
+        }
+        if !(self.flatness_threshold > 0.0 && self.flatness_threshold <= 1.0) {
+            return Err(Box::from(format!(
+                "Flatness threshold must be greater than 0 and not greater than 1, got {}",
+                self.flatness_threshold
+            )));
+        }
+        if !(self.peak_margin >= 0.0 && self.peak_margin.is_finite()) {
+            return Err(Box::from(format!(
+                "Peak margin must be finite and not negative, got {}",
+                self.peak_margin
+            )));
+        }
+        if self.max_peaks_per_second == 0 {
+            return Err(Box::from("Max peaks per second must be greater than zero"));
+        }
+        if let Some(preprocessor) = &self.preprocessor {
+            // chain without stages keeps samples as they are, but would be stored as a different configuration
+            if preprocessor.to_string().is_empty() {
+                return Err(Box::from(
+                    "Preprocessor must enable at least one stage, set it to None to switch preprocessing off",
+                ));
+            }
+            preprocessor.validate(self.sample_rate)?;
+        }
+        Ok(())
+    }
+
+    /// Maps band layout to frequency bins of configured sample rate and window size
+    ///
+    /// # Returns collection of band edges, the lowest first, each band spans bins above one edge
+    /// up to the next one
+    ///
+    pub fn band_edges(&self) -> Vec<usize> {
+        self.bands.edges(self.sample_rate, self.window_size)
+    }
+}
+
+/// Configuration string is stored in the repository next to indexed fingerprints
+///
+impl fmt::Display for FingerprintConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bands_key = match self.bands {
+            BandLayout::Bins(_) => "band_edges", // key of configuration stored before band layouts were added
+            _ => "bands",
+        };
+        write!(
+            f,
+            "sample_rate={};window_size={};hop_size={};{}={};fuzz_factor={};window={};silence_threshold={};flatness_threshold={};peak_margin={};max_peaks_per_second={}",
+            self.sample_rate,
+            self.window_size,
+            self.hop_size,
+            bands_key,
+            self.bands,
+            self.fuzz_factor,
+            self.window.name(),
+            self.silence_threshold,
+            self.flatness_threshold,
+            self.peak_margin,
+            self.max_peaks_per_second
+        )?;
+        // left out when switched off, so configuration stored before preprocessing was added keeps its string
+        match &self.preprocessor {
+            Some(preprocessor) => write!(f, ";preprocessor={}", preprocessor),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for FingerprintConfig {
+    type Err = Box<dyn Error>;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut config = FingerprintConfig::default();
+        for entry in text.split(';') {
+            let mut parts = entry.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("sample_rate"), Some(value)) => config.sample_rate = value.parse()?,
+                (Some("window_size"), Some(value)) => config.window_size = value.parse()?,
+                (Some("hop_size"), Some(value)) => config.hop_size = value.parse()?,
+                (Some("band_edges"), Some(value)) => {
+                    let edges: Result<Vec<usize>, _> = value.split(',').map(str::parse).collect();
+                    config.bands = BandLayout::Bins(edges?);
+                }
+                (Some("bands"), Some(value)) => config.bands = value.parse()?,
+                (Some("fuzz_factor"), Some(value)) => config.fuzz_factor = value.parse()?,
+                (Some("window"), Some(value)) => config.window = value.parse()?,
+                (Some("silence_threshold"), Some(value)) => {
+                    config.silence_threshold = value.parse()?
+                }
+                (Some("flatness_threshold"), Some(value)) => {
+                    config.flatness_threshold = value.parse()?
+                }
+                (Some("peak_margin"), Some(value)) => config.peak_margin = value.parse()?,
+                (Some("max_peaks_per_second"), Some(value)) => {
+                    config.max_peaks_per_second = value.parse()?
+                }
+                (Some("preprocessor"), Some(value)) => config.preprocessor = Some(value.parse()?),
+                _ => return Err(Box::from(format!("Invalid configuration entry: {}", entry))),
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BandLayout, FingerprintConfig, Preprocessor, WindowFunction};
+
+    #[test]
+    fn test_validate() {
+        assert!(FingerprintConfig::default().validate().is_ok());
+        let invalid = [
+            FingerprintConfig {
+                sample_rate: 0,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                window_size: 1000,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                hop_size: 0,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                hop_size: 2048,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                bands: BandLayout::Bins(vec![32, 40, 600]),
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                bands: BandLayout::Bins(vec![32, 32, 80]),
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                bands: BandLayout::Logarithmic {
+                    low: 300.0,
+                    high: 8000.0,
+                    bands: 6,
+                },
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                bands: BandLayout::Mel {
+                    low: 300.0,
+                    high: 320.0,
+                    bands: 6,
+                },
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                fuzz_factor: 0,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                silence_threshold: -1.0,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                flatness_threshold: 0.0,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                flatness_threshold: 1.5,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                peak_margin: f32::NAN,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                max_peaks_per_second: 0,
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                preprocessor: Some(Preprocessor {
+                    high_pass: Some(6000.0),
+                    ..Preprocessor::default()
+                }),
+                ..FingerprintConfig::default()
+            },
+            FingerprintConfig {
+                preprocessor: Some(Preprocessor {
+                    dc_blocker: false,
+                    high_pass: None,
+                    pre_emphasis: None,
+                    target_level: None,
+                }),
+                ..FingerprintConfig::default()
+            },
+        ];
+        for config in invalid.iter() {
+            assert!(config.validate().is_err());
+        }
+    }
+    #[test]
+    fn test_config_string() {
+        let config = FingerprintConfig {
+            sample_rate: 8000,
+            window_size: 2048,
+            hop_size: 2048,
+            bands: BandLayout::Bins(vec![10, 20, 900]),
+            fuzz_factor: 3,
+            window: WindowFunction::BlackmanHarris,
+            silence_threshold: 0.5,
+            flatness_threshold: 1.0,
+            peak_margin: 3.5,
+            max_peaks_per_second: 100,
+            preprocessor: None,
+        };
+        let text = config.to_string();
+        assert_eq!(
+            text,
+            "sample_rate=8000;window_size=2048;hop_size=2048;band_edges=10,20,900;fuzz_factor=3;window=blackman_harris;silence_threshold=0.5;flatness_threshold=1;peak_margin=3.5;max_peaks_per_second=100"
+        );
+        assert_eq!(text.parse::<FingerprintConfig>().unwrap(), config);
+        let mel = FingerprintConfig {
+            bands: BandLayout::Mel {
+                low: 300.0,
+                high: 3000.5,
+                bands: 8,
+            },
+            ..config
+        };
+        let text = mel.to_string();
+        assert!(text.contains(";bands=mel:300,3000.5,8;"));
+        assert_eq!(text.parse::<FingerprintConfig>().unwrap(), mel);
+        let preprocessed = FingerprintConfig {
+            preprocessor: Some(Preprocessor {
+                pre_emphasis: Some(0.97),
+                ..Preprocessor::default()
+            }),
+            ..FingerprintConfig::default()
+        };
+        let text = preprocessed.to_string();
+        assert!(text
+            .ends_with(";preprocessor=dc_blocker,high_pass:80,pre_emphasis:0.97,target_level:-20"));
+        assert_eq!(text.parse::<FingerprintConfig>().unwrap(), preprocessed);
+        assert!(format!("{};preprocessor=", FingerprintConfig::default())
+            .parse::<FingerprintConfig>()
+            .is_err());
+        assert!("preprocessor=dc_blocker,reverb:0.5"
+            .parse::<FingerprintConfig>()
+            .is_err());
+        // configuration stored before silence gate and peak picking were added keeps default thresholds
+        assert_eq!(
+            "sample_rate=11025;window_size=1024;hop_size=512;band_edges=32,40,80,120,180,320;fuzz_factor=2;window=hann"
+                .parse::<FingerprintConfig>()
+                .unwrap(),
+            FingerprintConfig::default()
+        );
+        assert!("window_size=2048;colour=blue"
+            .parse::<FingerprintConfig>()
+            .is_err());
+    }
+}
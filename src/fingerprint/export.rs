@@ -0,0 +1,261 @@
+use super::{FingerprintHandle, Landmark, Peak, Spectrogram};
+use crate::helpers::AudioBuffer;
+use png::{BitDepth, ColorType, Encoder};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::Write;
+
+const PEAK_COLOUR: [u8; 3] = [255, 0, 0]; // peaks extracted from the stream
+const MATCHED_COLOUR: [u8; 3] = [0, 255, 0]; // peaks which hashes matched a song
+const NPY_ALIGNMENT: usize = 64; // npy header together with magic string is padded to this number of bytes
+
+/// Debugging view of a fingerprinted stream
+///
+/// Holds spectrogram of the stream together with extracted peaks,
+/// and marks peaks that took part in landmarks matched against a song,
+/// so false negatives can be diagnosed offline.
+/// Spectrogram with peaks overlaid is exported to PNG,
+/// peaks are exported to CSV and spectrogram magnitudes to NPY.
+///
+pub struct PeakMapExport {
+    /// Magnitudes of each frame
+    pub spectrogram: Spectrogram,
+    /// Peaks extracted from the stream ordered by frame
+    pub peaks: Vec<Peak>,
+    /// Flag for each peak telling if the peak is a part of a matched landmark
+    pub matched: Vec<bool>,
+    /// Frequency bins of landmark hashes are rounded down to a multiple of this factor
+    fuzz_factor: usize,
+}
+
+impl PeakMapExport {
+    /// Create new instance of PeakMapExport
+    ///
+    /// # Arguments:
+    /// * handle - fingerprint handle the stream is analysed with
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    ///
+    /// # Returns success of PeakMapExport with no peaks matched, dynamic error otherwise
+    ///
+    pub fn new(
+        handle: &FingerprintHandle,
+        decoded_stream: &AudioBuffer,
+    ) -> Result<Self, Box<dyn Error>> {
+        let spectrogram = handle.calc_spectrogram(decoded_stream)?;
+        let peaks = handle.calc_peaks(decoded_stream)?;
+        let matched = vec![false; peaks.len()];
+        Ok(Self {
+            spectrogram,
+            peaks,
+            matched,
+            fuzz_factor: handle.config().fuzz_factor,
+        })
+    }
+
+    /// Marks anchor and target peaks of given landmarks as matched
+    ///
+    /// # Arguments:
+    /// * landmarks - landmarks of the stream which hashes matched a song,
+    ///   f.e. returned by `Repository::matching_landmarks`
+    ///
+    /// # Returns number of peaks marked as matched
+    ///
+    pub fn highlight(&mut self, landmarks: &[Landmark]) -> usize {
+        let fuzz_factor = self.fuzz_factor;
+        let mut points = HashSet::with_capacity(landmarks.len() * 2);
+        for landmark in landmarks.iter() {
+            let (anchor, target, delta) = landmark.hash.fields();
+            points.insert((landmark.offset, anchor));
+            points.insert((landmark.offset + delta, target));
+        }
+        for (peak, matched) in self.peaks.iter().zip(self.matched.iter_mut()) {
+            let bin = peak.bin - peak.bin % fuzz_factor;
+            if points.contains(&(peak.frame, bin)) {
+                *matched = true;
+            }
+        }
+        self.matched.iter().filter(|matched| **matched).count()
+    }
+
+    /// Renders spectrogram with peaks overlaid as RGB PNG image
+    ///
+    /// Each frame is a column and each frequency bin is a row, with low frequencies at the bottom.
+    /// Magnitudes are drawn in log scale grayscale, peaks are red and matched peaks are green.
+    ///
+    /// # Arguments:
+    /// * writer - destination of encoded image
+    ///
+    /// # Returns success if image is written, dynamic error otherwise
+    ///
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let width = self.spectrogram.magnitudes.len();
+        let height = self.spectrogram.window_size / 2;
+        if width == 0 {
+            return Err(Box::from("Stream is too short to render spectrogram"));
+        }
+        let levels: Vec<Vec<f32>> = self
+            .spectrogram
+            .magnitudes
+            .iter()
+            .map(|frame| frame.iter().map(|magnitude| magnitude.ln_1p()).collect())
+            .collect();
+        let top = levels
+            .iter()
+            .flatten()
+            .fold(0.0_f32, |top, level| top.max(*level));
+        let mut image = vec![0_u8; width * height * 3];
+        for (frame, frame_levels) in levels.iter().enumerate() {
+            for (bin, level) in frame_levels.iter().enumerate() {
+                let shade = if top > 0.0 {
+                    (level / top * 255.0) as u8
+                } else {
+                    0
+                };
+                paint(&mut image, width, height, frame, bin, [shade; 3]);
+            }
+        }
+        for (peak, matched) in self.peaks.iter().zip(self.matched.iter()) {
+            let colour = if *matched {
+                MATCHED_COLOUR
+            } else {
+                PEAK_COLOUR
+            };
+            paint(&mut image, width, height, peak.frame, peak.bin, colour);
+        }
+        let mut encoder = Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(ColorType::RGB);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&image)?;
+        Ok(())
+    }
+
+    /// Writes peaks as CSV with header row
+    ///
+    /// # Arguments:
+    /// * writer - destination of CSV rows
+    ///
+    /// # Returns success if all rows are written, dynamic error otherwise
+    ///
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        writeln!(writer, "frame,time,band,bin,frequency,magnitude,matched")?;
+        for (peak, matched) in self.peaks.iter().zip(self.matched.iter()) {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                peak.frame, peak.time, peak.band, peak.bin, peak.frequency, peak.magnitude, matched
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes spectrogram magnitudes as NPY array of little endian f32 with shape (frames, bins)
+    ///
+    /// # Arguments:
+    /// * writer - destination of the array
+    ///
+    /// # Returns success if array is written, dynamic error otherwise
+    ///
+    pub fn write_npy<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        let shape = (
+            self.spectrogram.magnitudes.len(),
+            self.spectrogram.window_size / 2,
+        );
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+            shape.0, shape.1
+        );
+        let prefix = 10; // magic string, version and header length
+        let padding = NPY_ALIGNMENT - (prefix + header.len() + 1) % NPY_ALIGNMENT;
+        header.push_str(&" ".repeat(padding % NPY_ALIGNMENT));
+        header.push('\n');
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for frame in self.spectrogram.magnitudes.iter() {
+            for magnitude in frame.iter() {
+                writer.write_all(&magnitude.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sets colour of a single pixel, frequency bin 0 is the bottom row
+///
+fn paint(image: &mut [u8], width: usize, height: usize, frame: usize, bin: usize, colour: [u8; 3]) {
+    if frame >= width || bin >= height {
+        return;
+    }
+    let idx = ((height - 1 - bin) * width + frame) * 3;
+    image[idx..idx + 3].copy_from_slice(&colour);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PeakMapExport;
+    use crate::fingerprint::{FingerprintConfig, FingerprintHandle};
+    use crate::helpers::AudioBuffer;
+    use std::f32::consts::PI;
+
+    fn chirp(sample_rate: u32) -> AudioBuffer {
+        let samples = (0..sample_rate as usize * 2)
+            .map(|t| {
+                let time = t as f32 / sample_rate as f32;
+                10000.0 * (2.0 * PI * (300.0 + 400.0 * time) * time).sin()
+            })
+            .collect();
+        AudioBuffer::new(samples, sample_rate)
+    }
+
+    #[test]
+    fn test_highlight_and_csv() {
+        let config = FingerprintConfig::default();
+        let handle = FingerprintHandle::new(config.clone()).unwrap();
+        let stream = chirp(config.sample_rate);
+        let landmarks = handle.calc_fingerprint_collection(&stream).unwrap();
+        let mut export = PeakMapExport::new(&handle, &stream).unwrap();
+        assert_eq!(export.highlight(&[]), 0);
+        assert!(export.highlight(&landmarks[..1]) >= 2); // fuzzed bins of neighbouring bands may coincide
+        let marked = export.highlight(&landmarks);
+        assert!(marked > 2 && marked <= export.peaks.len());
+
+        let mut csv = Vec::new();
+        export.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "frame,time,band,bin,frequency,magnitude,matched");
+        assert_eq!(lines.len(), export.peaks.len() + 1);
+        assert_eq!(
+            lines.iter().filter(|line| line.ends_with(",true")).count(),
+            marked
+        );
+    }
+    #[test]
+    fn test_png_and_npy() {
+        let config = FingerprintConfig::default();
+        let handle = FingerprintHandle::new(config.clone()).unwrap();
+        let export = PeakMapExport::new(&handle, &chirp(config.sample_rate)).unwrap();
+        let frames = export.spectrogram.magnitudes.len();
+
+        let mut png = Vec::new();
+        export.write_png(&mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(&png[16..20], &(frames as u32).to_be_bytes());
+        assert_eq!(&png[20..24], &(config.window_size as u32 / 2).to_be_bytes());
+
+        let mut npy = Vec::new();
+        export.write_npy(&mut npy).unwrap();
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+        assert!(header.contains(&format!(
+            "'shape': ({}, {})",
+            frames,
+            config.window_size / 2
+        )));
+        assert_eq!(
+            npy.len(),
+            10 + header_len + frames * config.window_size / 2 * 4
+        );
+    }
+}
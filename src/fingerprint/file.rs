@@ -0,0 +1,298 @@
+use super::{AlgorithmId, FingerprintConfig, FingerprintHandle, Hash, Landmark};
+use crate::helpers::AudioBuffer;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{Read, Write};
+
+const MAGIC: [u8; 4] = *b"CRFP"; // first bytes of every fingerprint file
+const FORMAT_VERSION: u16 = 1; // version of the file layout, has to be increased on every change of it
+const MAX_TEXT_LENGTH: usize = 1 << 16; // longest configuration or song name accepted, guards against corrupted lengths
+const MAX_RESERVED_LANDMARKS: usize = 1 << 16; // landmarks reserved up front, a corrupted count does not allocate more
+
+/// Fingerprints of a single song stored in a compact binary file
+///
+/// Songs can be fingerprinted on one machine, shipped as files and ingested
+/// in to a repository or compared with other files elsewhere.
+/// All numbers are little endian, varint is unsigned LEB128.
+///
+/// | bytes  | field                                                    |
+/// |--------|----------------------------------------------------------|
+/// | 4      | magic string `CRFP`                                      |
+/// | 2      | version of the file layout                               |
+/// | 4      | version of the fingerprint algorithm                     |
+/// | 8      | hash of the algorithm parameters                         |
+/// | 4      | sample rate of the source stream                         |
+/// | 4      | duration of the source stream in seconds, `f32`          |
+/// | varint | length of configuration followed by its UTF-8 string     |
+/// | varint | length of author and title followed by its UTF-8 string  |
+/// | varint | number of landmarks                                      |
+///
+/// Landmarks follow ordered by offset and hash, each as two varints:
+/// offset delta from the previous landmark, then hash delta from the previous landmark
+/// if both share the offset, or the whole hash if the offset changes.
+/// Most landmarks take one byte of offset and a few bytes of hash.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct FingerprintFile {
+    /// Identifier of the algorithm the landmarks were calculated with
+    pub algorithm: AlgorithmId,
+    /// Parameters the landmarks were calculated with
+    pub config: FingerprintConfig,
+    /// Number of samples per second of the source stream, before it was resampled
+    pub sample_rate: u32,
+    /// Duration of the source stream in seconds
+    pub duration: f32,
+    /// Author and title of a song in one string
+    pub song: String,
+    /// Landmarks ordered by offset and hash
+    pub landmarks: Vec<Landmark>,
+}
+
+impl FingerprintFile {
+    /// Create new instance of FingerprintFile by fingerprinting the stream
+    ///
+    /// # Arguments:
+    /// * handle - fingerprint handle the stream is fingerprinted with
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    /// * song - author and title of a song in one string
+    ///
+    /// # Returns success of FingerprintFile, dynamic error otherwise
+    ///
+    pub fn new(
+        handle: &FingerprintHandle,
+        decoded_stream: &AudioBuffer,
+        song: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut landmarks = handle.calc_fingerprint_collection(decoded_stream)?;
+        landmarks.sort_by_key(|landmark| (landmark.offset, landmark.hash));
+        Ok(Self {
+            algorithm: AlgorithmId::new(handle.config()),
+            config: handle.config().clone(),
+            sample_rate: decoded_stream.sample_rate,
+            duration: decoded_stream.duration(),
+            song: song.to_string(),
+            landmarks,
+        })
+    }
+
+    /// Checks if landmarks of the file can be matched against landmarks calculated with given configuration
+    ///
+    /// File written by a different version of the algorithm is read without error,
+    /// but its hashes are never comparable with hashes calculated by this version.
+    ///
+    /// # Arguments:
+    /// * config - parameters the other landmarks are calculated with, f.e. configuration of the file itself
+    ///
+    /// # Returns success if landmarks are comparable, dynamic Error otherwise
+    ///
+    pub fn check_compatibility(&self, config: &FingerprintConfig) -> Result<(), Box<dyn Error>> {
+        let expected = AlgorithmId::new(config);
+        if self.algorithm != expected {
+            return Err(Box::from(format!(
+                "Fingerprint file of {} is calculated with algorithm {}, expected algorithm {}",
+                self.song, self.algorithm, expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes fingerprints in binary format
+    ///
+    /// # Arguments:
+    /// * writer - destination of the file, f.e. `File` or `Vec<u8>`
+    ///
+    /// # Returns success if whole file is written, dynamic error otherwise
+    ///
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        let mut landmarks = self.landmarks.clone();
+        landmarks.sort_by_key(|landmark| (landmark.offset, landmark.hash));
+        let mut buffer = Vec::with_capacity(64 + landmarks.len() * 6);
+        buffer.extend_from_slice(&MAGIC);
+        buffer.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        buffer.extend_from_slice(&self.algorithm.version.to_le_bytes());
+        buffer.extend_from_slice(&self.algorithm.parameters.to_le_bytes());
+        buffer.extend_from_slice(&self.sample_rate.to_le_bytes());
+        buffer.extend_from_slice(&self.duration.to_le_bytes());
+        write_text(&mut buffer, &self.config.to_string())?;
+        write_text(&mut buffer, &self.song)?;
+        write_varint(&mut buffer, landmarks.len() as u64);
+        let (mut offset, mut hash) = (0, 0);
+        for landmark in landmarks.iter() {
+            write_varint(&mut buffer, (landmark.offset - offset) as u64);
+            if landmark.offset != offset {
+                hash = 0;
+            }
+            write_varint(&mut buffer, u64::from(landmark.hash.value() - hash));
+            offset = landmark.offset;
+            hash = landmark.hash.value();
+        }
+        writer.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Reads fingerprints written in binary format
+    ///
+    /// # Arguments:
+    /// * reader - source of the file, f.e. `File` or `&[u8]`
+    ///
+    /// # Returns success of FingerprintFile, dynamic error if the file is truncated, corrupted
+    /// or written in a newer layout
+    ///
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut header = [0; 26];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(Box::from("Not a fingerprint file"));
+        }
+        let field = |start: usize, end: usize| {
+            let mut bytes = [0; 8];
+            bytes[..end - start].copy_from_slice(&header[start..end]);
+            u64::from_le_bytes(bytes)
+        };
+        let version = field(4, 6) as u16;
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(Box::from(format!(
+                "Unsupported fingerprint file version {}, supported up to {}",
+                version, FORMAT_VERSION
+            )));
+        }
+        let algorithm = AlgorithmId {
+            version: field(6, 10) as u32,
+            parameters: field(10, 18),
+        };
+        let sample_rate = field(18, 22) as u32;
+        let duration = f32::from_bits(field(22, 26) as u32);
+        let config = read_text(&mut reader)?.parse()?;
+        let song = read_text(&mut reader)?;
+        let count = read_varint(&mut reader)? as usize;
+        let mut landmarks = Vec::with_capacity(count.min(MAX_RESERVED_LANDMARKS));
+        let (mut offset, mut hash) = (0_usize, 0_u32);
+        for _ in 0..count {
+            let offset_delta = read_varint(&mut reader)?;
+            if offset_delta > 0 {
+                hash = 0;
+            }
+            offset = offset
+                .checked_add(offset_delta as usize)
+                .ok_or("Landmark offset overflow in fingerprint file")?;
+            hash = u32::try_from(u64::from(hash) + read_varint(&mut reader)?)
+                .map_err(|_| "Landmark hash overflow in fingerprint file")?;
+            landmarks.push(Landmark {
+                hash: Hash::from(hash),
+                offset,
+            });
+        }
+        Ok(Self {
+            algorithm,
+            config,
+            sample_rate,
+            duration,
+            song,
+            landmarks,
+        })
+    }
+}
+
+/// Appends unsigned LEB128 varint, seven bits per byte with the lowest bits first
+///
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64, Box<dyn Error>> {
+    let mut value = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        let byte = byte[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Box::from("Varint too long in fingerprint file"))
+}
+
+fn write_text(buffer: &mut Vec<u8>, text: &str) -> Result<(), Box<dyn Error>> {
+    if text.len() > MAX_TEXT_LENGTH {
+        return Err(Box::from(format!(
+            "Text of {} bytes is too long for fingerprint file, max {}",
+            text.len(),
+            MAX_TEXT_LENGTH
+        )));
+    }
+    write_varint(buffer, text.len() as u64);
+    buffer.extend_from_slice(text.as_bytes());
+    Ok(())
+}
+
+fn read_text<R: Read>(reader: &mut R) -> Result<String, Box<dyn Error>> {
+    let length = read_varint(reader)? as usize;
+    if length > MAX_TEXT_LENGTH {
+        return Err(Box::from(format!(
+            "Text of {} bytes is too long in fingerprint file, max {}",
+            length, MAX_TEXT_LENGTH
+        )));
+    }
+    let mut text = vec![0; length];
+    reader.read_exact(&mut text)?;
+    Ok(String::from_utf8(text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FingerprintFile;
+    use crate::data::memory_actions::MemoryRepository;
+    use crate::data::Repository;
+    use crate::fingerprint::{FingerprintConfig, FingerprintHandle};
+    use crate::helpers::SignalGenerator;
+
+    #[test]
+    fn test_write_and_read() {
+        let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+        let song = SignalGenerator::new(2, 22050).song(10.0, 0.5);
+        let file = FingerprintFile::new(&handle, &song, "Generator - Song 2").unwrap();
+        assert!(!file.landmarks.is_empty());
+        assert_eq!(file.sample_rate, 22050);
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+        // smaller than a plain pair of 32 bit hash and 32 bit offset per landmark
+        assert!(bytes.len() < file.landmarks.len() * 8);
+        let read = FingerprintFile::read(&bytes[..]).unwrap();
+        assert_eq!(read, file);
+        read.check_compatibility(handle.config()).unwrap();
+
+        let mut repository = MemoryRepository::new();
+        repository.ingest(&read).unwrap();
+        let located = repository
+            .locate_matches(handle.config(), &file.landmarks[100..])
+            .unwrap();
+        assert_eq!(located[0].song, "Generator - Song 2");
+    }
+    #[test]
+    fn test_invalid_files() {
+        let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+        let song = SignalGenerator::new(3, 11025).song(2.0, 0.5);
+        let file = FingerprintFile::new(&handle, &song, "Generator - Song 3").unwrap();
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+
+        assert!(FingerprintFile::read(&bytes[..bytes.len() - 1]).is_err());
+        let mut foreign = bytes.clone();
+        foreign[..4].copy_from_slice(b"RIFF");
+        assert!(FingerprintFile::read(&foreign[..]).is_err());
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert!(FingerprintFile::read(&newer[..]).is_err());
+        // file of other algorithm version is readable, but never ingested
+        let mut other = bytes.clone();
+        other[6] = 99;
+        let other = FingerprintFile::read(&other[..]).unwrap();
+        assert!(other.check_compatibility(handle.config()).is_err());
+        assert!(MemoryRepository::new().ingest(&other).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+const DELTA_BITS: u32 = 12; // bits of frames delta between anchor and target peak
+const BIN_BITS: u32 = 10; // bits of a single frequency bin
+const TARGET_SHIFT: u32 = DELTA_BITS;
+const ANCHOR_SHIFT: u32 = DELTA_BITS + BIN_BITS;
+
+/// Upper limit (exclusive) of frequency bin that fits the hash
+pub const MAX_BIN: usize = 1 << BIN_BITS;
+/// Upper limit (exclusive) of frames delta that fits the hash
+pub const MAX_DELTA: usize = 1 << DELTA_BITS;
+
+/// Landmark hash packed in to 32 bits
+///
+/// Layout, from the most significant bit:
+///
+/// | bits    | field                                  |
+/// |---------|----------------------------------------|
+/// | 31 - 22 | frequency bin of the anchor peak       |
+/// | 21 - 12 | frequency bin of the target peak       |
+/// | 11 - 0  | frames delta between anchor and target |
+///
+/// Each field has its own range of bits and values out of the field range are refused,
+/// so two different landmarks never share the hash.
+/// The same layout is used on every target, hash is stored as a decimal number of `u32`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash(u32);
+
+impl Hash {
+    /// Packs landmark fields in to the hash
+    ///
+    /// # Arguments:
+    /// * anchor - frequency bin of the anchor peak, lower than MAX_BIN
+    /// * target - frequency bin of the target peak, lower than MAX_BIN
+    /// * delta - frames delta between anchor and target peak, lower than MAX_DELTA
+    ///
+    /// # Returns Option with the hash, None if any of the fields does not fit its range
+    ///
+    pub fn new(anchor: usize, target: usize, delta: usize) -> Option<Self> {
+        if anchor >= MAX_BIN || target >= MAX_BIN || delta >= MAX_DELTA {
+            return None;
+        }
+        Some(Self(
+            (anchor as u32) << ANCHOR_SHIFT | (target as u32) << TARGET_SHIFT | delta as u32,
+        ))
+    }
+
+    /// Unpacks landmark fields from the hash
+    ///
+    /// # Returns tuple of anchor frequency bin, target frequency bin and frames delta
+    ///
+    pub fn fields(&self) -> (usize, usize, usize) {
+        (
+            (self.0 >> ANCHOR_SHIFT) as usize & (MAX_BIN - 1),
+            (self.0 >> TARGET_SHIFT) as usize & (MAX_BIN - 1),
+            self.0 as usize & (MAX_DELTA - 1),
+        )
+    }
+
+    /// Raw value of the hash
+    ///
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for Hash {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hash, MAX_BIN, MAX_DELTA};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_layout() {
+        let hash = Hash::new(32, 45, 1).unwrap();
+        assert_eq!(hash.value(), 32 << 22 | 45 << 12 | 1);
+        assert_eq!(hash.fields(), (32, 45, 1));
+        assert_eq!(hash.to_string(), "0802d001");
+        let top = Hash::new(MAX_BIN - 1, MAX_BIN - 1, MAX_DELTA - 1).unwrap();
+        assert_eq!(top.value(), u32::MAX);
+        assert_eq!(Hash::from(top.value()), top);
+    }
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(Hash::new(MAX_BIN, 0, 0), None);
+        assert_eq!(Hash::new(0, MAX_BIN, 0), None);
+        assert_eq!(Hash::new(0, 0, MAX_DELTA), None);
+    }
+    #[test]
+    fn test_no_collisions() {
+        let mut hashes = HashSet::new();
+        for anchor in (0..MAX_BIN).step_by(31) {
+            for target in (0..MAX_BIN).step_by(29) {
+                for delta in 0..8 {
+                    let hash = Hash::new(anchor, target, delta).unwrap();
+                    assert_eq!(hash.fields(), (anchor, target, delta));
+                    assert!(hashes.insert(hash));
+                }
+            }
+        }
+    }
+}
@@ -1,29 +1,159 @@
+use super::helpers::{AudioBuffer, Sample};
 use rayon::prelude::*;
-use rustfft::algorithm::Radix4;
 use rustfft::num_complex::Complex;
 use rustfft::num_traits::Zero;
-use rustfft::FFT;
+use std::borrow::Cow;
 use std::error::Error;
-use std::sync::{Arc, Mutex};
 
-const FFT_WINDOW_SIZE: usize = 1024; // chunk window size to process by fast forward fourier function
-const FREQ_BINS: &[usize] = &[32, 40, 80, 120, 180, 320]; // Each value in array is a top range frequency to calculate local maximum magnitude for
-const FUZZ_FACTOR: usize = 2; // higher the value of this factor, lower the fingerprint entropy, and less bias the algorithm become to the sound noises
+mod bands;
+mod chroma;
+mod compare;
+mod config;
+#[cfg(feature = "png")]
+mod export;
+mod file;
+mod hash;
+mod picker;
+mod real_fft;
+mod speed;
+mod streaming;
+mod version;
+mod window;
+pub use bands::BandLayout;
+pub use chroma::{Chroma, ChromaSimilarity, Chromagram};
+pub use compare::Similarity;
+pub use config::FingerprintConfig;
+#[cfg(feature = "png")]
+pub use export::PeakMapExport;
+pub use file::FingerprintFile;
+pub use hash::Hash;
+use picker::{median, PeakPicker};
+use real_fft::{FftScratch, RealFft};
+pub use speed::{SpeedSearch, SpeedVariant};
+pub use streaming::StreamingFingerprinter;
+pub use version::{AlgorithmId, ALGORITHM_VERSION};
+pub use window::WindowFunction;
+
+const TARGET_ZONE: usize = 3; // number of frames following the anchor frame that are searched for target peaks
+const FAN_OUT: usize = 5; // max number of target peaks paired with a single anchor peak
+
+/// Frame index with peaks of that frame
+type FramePeaks = (usize, Vec<Peak>);
+
+/// Spectral peak, the point of max magnitude in one of the frequency bands of a frame
+///
+/// Peaks of all frames make the constellation map landmarks are calculated from.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    /// Index of the frame the peak is found in
+    pub frame: usize,
+    /// Time of the frame start in seconds
+    pub time: f32,
+    /// Index of the frequency band the peak is found in
+    pub band: usize,
+    /// Frequency bin of the fast fourier transform output
+    pub bin: usize,
+    /// Frequency of the bin in Hz
+    pub frequency: f32,
+    /// Magnitude of the bin
+    pub magnitude: f32,
+}
+
+/// Acoustic fingerprint anchored in time
+///
+/// Landmark is a hash of a pair of spectral peaks, the anchor peak and the target peak
+/// found shortly after it, together with the time distance between them.
+/// Offset is the frame index of the anchor peak, so matching songs can be verified
+/// by time alignment of the landmarks instead of a plain hash count.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Landmark {
+    /// Hash of anchor peak, target peak and time delta between them
+    pub hash: Hash,
+    /// Frame index of the anchor peak
+    pub offset: usize,
+}
+
+/// Landmarks of a stream together with frame statistics
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fingerprints {
+    /// Landmarks ordered by offset
+    pub landmarks: Vec<Landmark>,
+    /// Number of frames in the stream
+    pub frames: usize,
+    /// Number of frames dropped by silence gate
+    pub silent_frames: usize,
+}
+
+/// Magnitudes of fast fourier transform output for each frame
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spectrogram {
+    /// Magnitudes of frequency bins from 0 to half of window size for each frame
+    pub magnitudes: Vec<Vec<f32>>,
+    /// Sample rate the stream was resampled to
+    pub sample_rate: u32,
+    /// Number of samples in one frame
+    pub window_size: usize,
+    /// Number of samples between starts of consecutive frames
+    pub hop_size: usize,
+}
+
+/// Buffers reused between frames processed on the same thread,
+/// so frame processing does not allocate memory for the transform
+///
+struct FrameScratch {
+    /// Buffers of the transform
+    fft: FftScratch,
+    /// Frequency bins from 0 to half of window size inclusive
+    spectrum: Vec<Complex<f32>>,
+    /// Magnitudes of frequency bins
+    magnitudes: Vec<f32>,
+    /// Magnitudes of a single band sorted for median
+    sorted: Vec<f32>,
+}
 
 /// Helper struct for calculating acoustic fingerprint
 ///
 #[allow(dead_code)]
 pub struct FingerprintHandle {
-    /// FFT algorithm
-    fft: Radix4<f32>,
+    /// FFT algorithm for real input
+    fft: RealFft,
+    /// Coefficients of window function frame samples are multiplied by
+    window: Vec<f32>,
+    /// Top frequency bin of each band, the lowest edge first
+    band_edges: Vec<usize>,
+    /// Parameters of fingerprint calculation
+    config: FingerprintConfig,
 }
 
 #[allow(dead_code)]
 impl FingerprintHandle {
-    pub fn new() -> FingerprintHandle {
-        FingerprintHandle {
-            fft: Radix4::new(FFT_WINDOW_SIZE, false),
-        }
+    /// Create new instance of FingerprintHandle
+    ///
+    /// # Arguments:
+    /// * config - parameters of fingerprint calculation
+    ///
+    /// # Returns success of FingerprintHandle if configuration is valid, dynamic error otherwise
+    ///
+    pub fn new(config: FingerprintConfig) -> Result<FingerprintHandle, Box<dyn Error>> {
+        config.validate()?;
+        Ok(FingerprintHandle {
+            fft: RealFft::new(config.window_size),
+            window: config.window.coefficients(config.window_size),
+            band_edges: config.band_edges(),
+            config,
+        })
+    }
+
+    /// Getter for configuration
+    ///
+    /// # Returns parameters the fingerprints are calculated with
+    ///
+    pub fn config(&self) -> &FingerprintConfig {
+        &self.config
     }
 
     /// Calculate fingerprint for decoded stream
@@ -31,93 +161,1029 @@ impl FingerprintHandle {
     /// This method uses fast forward fourier computation
     /// to process decoded stream input in to
     /// stream of complex number output,
-    /// then calculates fingerprint
+    /// then finds spectral peaks of each frame and pairs them in to landmarks.
+    /// Each frame is multiplied by configured window function before the transform.
+    /// Frames start every hop size samples and overlap when hop size is smaller than window size.
+    /// Frames are processed in parallel, but collected in frame order,
+    /// so the result is the same for every run.
+    /// Stream is resampled to configured sample rate first,
+    /// so the same frequency bins are compared regardless of the source sample rate
     ///
     /// # Arguments:
-    /// * decoded_stream - acoustic stream that is decoded to stream of floats
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
     ///
-    /// # Returns success of fingerprint collection, dynamic error otherwise
+    /// # Returns success of landmarks collection ordered by offset, dynamic error otherwise
     ///
     pub fn calc_fingerprint_collection(
+        &self,
+        decoded_stream: &AudioBuffer,
+    ) -> Result<Vec<Landmark>, Box<dyn Error>> {
+        Ok(self.calc_fingerprints(decoded_stream)?.landmarks)
+    }
+
+    /// Calculate fingerprint for PCM samples of any format and number of channels
+    ///
+    /// Samples are scaled and mixed down to mono internally, so buffers of audio APIs
+    /// can be fingerprinted without converting them first.
+    /// The same audio gives the same landmarks in every sample format.
+    ///
+    /// # Arguments:
+    /// * samples - interleaved samples, `i16`, `i32`, or `f32` and `f64` in range from -1 to 1
+    /// * channels - number of interleaved channels
+    /// * sample_rate - number of samples of a single channel per second
+    ///
+    /// # Returns success of landmarks collection ordered by offset, dynamic error otherwise
+    ///
+    pub fn calc_pcm_fingerprint_collection<S: Sample>(
+        &self,
+        samples: &[S],
+        channels: usize,
+        sample_rate: u32,
+    ) -> Result<Vec<Landmark>, Box<dyn Error>> {
+        self.calc_fingerprint_collection(&AudioBuffer::from_interleaved(
+            samples,
+            channels,
+            sample_rate,
+        )?)
+    }
+
+    /// Calculate fingerprint for decoded stream and count frames skipped by silence gate
+    ///
+    /// Frames which samples are quieter than configured silence threshold,
+    /// or which spectrum is flatter than configured flatness threshold, produce no peaks,
+    /// so degenerate landmarks of silence and background noise are never calculated
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    ///
+    /// # Returns success of fingerprints with landmarks ordered by offset, dynamic error otherwise
+    ///
+    pub fn calc_fingerprints(
+        &self,
+        decoded_stream: &AudioBuffer,
+    ) -> Result<Fingerprints, Box<dyn Error>> {
+        let samples = self.canonical_samples(decoded_stream)?;
+        let (peaks, silent_frames) =
+            self.calc_frame_peaks(&samples, 0, &mut PeakPicker::new(&self.config));
+        Ok(Fingerprints {
+            landmarks: pair_peaks(&peaks, self.config.fuzz_factor),
+            frames: peaks.len(),
+            silent_frames,
+        })
+    }
+
+    /// Calculate constellation map of decoded stream
+    ///
+    /// Uses the same pipeline as fingerprint calculation, but returns spectral peaks
+    /// landmarks are made of, so they can be visualised, inspected or hashed differently
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    ///
+    /// # Returns success of peaks collection ordered by frame and band, dynamic error otherwise
+    ///
+    pub fn calc_peaks(&self, decoded_stream: &AudioBuffer) -> Result<Vec<Peak>, Box<dyn Error>> {
+        let samples = self.canonical_samples(decoded_stream)?;
+        Ok(self
+            .calc_frame_peaks(&samples, 0, &mut PeakPicker::new(&self.config))
+            .0
+            .into_iter()
+            .flat_map(|(_, peaks)| peaks)
+            .collect())
+    }
+
+    /// Compare landmarks of two clips without storing any of them in a repository,
+    /// f.e. to check if an edit or a remaster still matches the original
+    ///
+    /// # Arguments:
+    /// * first - landmarks of the first clip calculated by this handle
+    /// * second - landmarks of the second clip calculated by this handle
+    ///
+    /// # Returns similarity with score, offset of the second clip in the first and matched duration
+    ///
+    pub fn compare(&self, first: &[Landmark], second: &[Landmark]) -> Similarity {
+        let frame_duration = self.config.hop_size as f32 / self.config.sample_rate as f32;
+        Similarity::new(first, second, frame_duration)
+    }
+
+    /// Calculate landmarks of decoded stream for each of searched playback speeds
+    ///
+    /// Speeding up a song shifts every frequency bin and shortens time,
+    /// so landmarks of sped up sample do not match hashes of the indexed song.
+    /// Peaks are calculated once, then for each speed factor frequency bins are divided
+    /// and offsets multiplied by the factor before pairing peaks in to landmarks,
+    /// which maps the sample back to time and frequency scale of the song.
+    /// Frames delta of landmarks is left as it is, as it is only a few frames long
+    /// and does not change for speed factors close to 1.
+    /// Peaks scaled out of configured band range are left out before pairing,
+    /// as the indexed song has no peaks there, and they would not fit the hash.
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    /// * search - range of speed factors to search
+    ///
+    /// # Returns success of landmarks for each speed factor, dynamic error otherwise
+    ///
+    pub fn calc_speed_variants(
+        &self,
+        decoded_stream: &AudioBuffer,
+        search: &SpeedSearch,
+    ) -> Result<Vec<SpeedVariant>, Box<dyn Error>> {
+        search.validate()?;
+        let samples = self.canonical_samples(decoded_stream)?;
+        let (peaks, _) = self.calc_frame_peaks(&samples, 0, &mut PeakPicker::new(&self.config));
+        let band_range = self.band_edges[0] + 1..=self.band_edges[self.band_edges.len() - 1];
+        Ok(search
+            .factors()
+            .into_iter()
+            .map(|speed| {
+                let scaled: Vec<FramePeaks> = peaks
+                    .iter()
+                    .map(|(frame, points)| {
+                        let points = points
+                            .iter()
+                            .map(|peak| Peak {
+                                bin: (peak.bin as f32 / speed).round() as usize,
+                                ..*peak
+                            })
+                            .filter(|peak| band_range.contains(&peak.bin))
+                            .collect();
+                        (*frame, points)
+                    })
+                    .collect();
+                let landmarks = pair_peaks(&scaled, self.config.fuzz_factor)
+                    .into_iter()
+                    .map(|landmark| Landmark {
+                        offset: (landmark.offset as f32 * speed).round() as usize,
+                        ..landmark
+                    })
+                    .collect();
+                SpeedVariant { speed, landmarks }
+            })
+            .collect())
+    }
+
+    /// Calculate spectrogram of decoded stream
+    ///
+    /// Uses the same frames, window function and transform as fingerprint calculation
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    ///
+    /// # Returns success of spectrogram, dynamic error otherwise
+    ///
+    pub fn calc_spectrogram(
+        &self,
+        decoded_stream: &AudioBuffer,
+    ) -> Result<Spectrogram, Box<dyn Error>> {
+        let samples = self.canonical_samples(decoded_stream)?;
+        let window_size = self.config.window_size;
+        let hop_size = self.config.hop_size;
+        let magnitudes = (0..frames_count(samples.len(), window_size, hop_size))
+            .into_par_iter()
+            .map_init(
+                || self.scratch(),
+                |scratch, frame| {
+                    self.frame_magnitudes(&samples, frame, scratch);
+                    scratch.magnitudes[..window_size / 2].to_vec()
+                },
+            )
+            .collect();
+        Ok(Spectrogram {
+            magnitudes,
+            sample_rate: self.config.sample_rate,
+            window_size,
+            hop_size,
+        })
+    }
+
+    /// Calculate pitch class profile of decoded stream for cover and live version detection
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    ///
+    /// # Returns success of chromagram, dynamic error otherwise
+    ///
+    pub fn calc_chromagram(
+        &self,
+        decoded_stream: &AudioBuffer,
+    ) -> Result<Chromagram, Box<dyn Error>> {
+        Ok(Chromagram::new(&self.calc_spectrogram(decoded_stream)?))
+    }
+
+    /// Resamples stream to configured sample rate if needed and applies configured preprocessing
+    ///
+    fn canonical_samples<'a>(
+        &self,
+        decoded_stream: &'a AudioBuffer,
+    ) -> Result<Cow<'a, [f32]>, Box<dyn Error>> {
+        if decoded_stream.sample_rate == 0 {
+            return Err(Box::from("Invalid sample rate of decoded stream"));
+        }
+        let resampled = if decoded_stream.sample_rate == self.config.sample_rate {
+            Cow::Borrowed(decoded_stream)
+        } else {
+            Cow::Owned(decoded_stream.resample(self.config.sample_rate))
+        };
+        match &self.config.preprocessor {
+            Some(preprocessor) => Ok(Cow::Owned(preprocessor.process(&resampled)?.samples)),
+            None => Ok(match resampled {
+                Cow::Borrowed(stream) => Cow::Borrowed(&stream.samples),
+                Cow::Owned(stream) => Cow::Owned(stream.samples),
+            }),
+        }
+    }
+
+    /// Calculates peaks of all full frames of the stream in configured sample rate
+    ///
+    /// Loudest bins of frame bands are calculated in parallel,
+    /// then peak picker selects peaks standing out of the noise floor frame by frame
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream starting at the first frame
+    /// * first_frame - index of the first frame of the stream
+    /// * picker - peak picker holding noise floor of preceding frames
+    ///
+    /// # Returns collection of frame index and peaks of that frame, ordered by frame index,
+    /// together with number of frames skipped by silence gate, which have no peaks
+    ///
+    fn calc_frame_peaks(
         &self,
         decoded_stream: &[f32],
-    ) -> Result<Vec<usize>, Box<dyn Error>> {
-        let fingerprints_arr: Arc<Mutex<Vec<usize>>> = Arc::new(Mutex::new(Vec::new()));
-        decoded_stream
-            .par_chunks(FFT_WINDOW_SIZE) // multi threaded iteration over chunks, where chunk of size FFT_WINDOW_SIZE
-            .for_each(|chunk| {
-                if chunk.len() >= FFT_WINDOW_SIZE {
-                    let mut input: Vec<Complex<f32>> = chunk.iter().map(Complex::from).collect();
-                    let mut output: Vec<Complex<f32>> = vec![Complex::zero(); FFT_WINDOW_SIZE];
-                    self.fft.process(&mut input, &mut output);
-                    fingerprints_arr
-                        .lock()
-                        .unwrap()
-                        .push(calculate_fingerprint(&output));
+        first_frame: usize,
+        picker: &mut PeakPicker,
+    ) -> (Vec<FramePeaks>, usize) {
+        let window_size = self.config.window_size;
+        let hop_size = self.config.hop_size;
+        let candidates: Vec<Option<(Vec<Peak>, Vec<f32>)>> =
+            (0..frames_count(decoded_stream.len(), window_size, hop_size))
+                .into_par_iter() // multi threaded iteration over frames, where frame of size window_size starts every hop_size samples
+                .map_init(
+                    || self.scratch(),
+                    |scratch, frame| {
+                        let chunk =
+                            &decoded_stream[frame * hop_size..frame * hop_size + window_size];
+                        if rms(chunk) < self.config.silence_threshold {
+                            return None;
+                        }
+                        self.frame_magnitudes(decoded_stream, frame, scratch);
+                        let frame = first_frame + frame;
+                        let band_edges = &self.band_edges;
+                        let magnitudes = &scratch.magnitudes;
+                        let banded =
+                            &magnitudes[band_edges[0] + 1..=band_edges[band_edges.len() - 1]];
+                        if spectral_flatness(banded) > self.config.flatness_threshold {
+                            return None;
+                        }
+                        let peaks = calculate_peaks(magnitudes, band_edges)
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, (bin, _))| *bin != 0)
+                            .map(|(band, (bin, magnitude))| Peak {
+                                frame,
+                                time: (frame * hop_size) as f32 / self.config.sample_rate as f32,
+                                band,
+                                bin,
+                                frequency: (bin * self.config.sample_rate as usize) as f32
+                                    / window_size as f32,
+                                magnitude,
+                            })
+                            .collect();
+                        Some((
+                            peaks,
+                            band_medians(magnitudes, band_edges, &mut scratch.sorted),
+                        ))
+                    },
+                )
+                .collect(); // indexed parallel iterator keeps frame order when collecting
+        let mut silent_frames = 0;
+        let peaks = candidates
+            .into_iter()
+            .enumerate()
+            .map(|(frame, candidate)| match candidate {
+                Some((peaks, medians)) => (first_frame + frame, picker.pick(peaks, medians)),
+                None => {
+                    silent_frames += 1;
+                    (first_frame + frame, Vec::new())
                 }
-            });
-        let arr = fingerprints_arr.lock().unwrap().clone();
-        Ok(arr)
+            })
+            .collect();
+        (peaks, silent_frames)
+    }
+
+    /// Creates buffers for processing frames on a single thread
+    ///
+    fn scratch(&self) -> FrameScratch {
+        let bins = self.config.window_size / 2 + 1;
+        FrameScratch {
+            fft: self.fft.scratch(),
+            spectrum: vec![Complex::zero(); bins],
+            magnitudes: vec![0.0; bins],
+            sorted: Vec::with_capacity(bins),
+        }
+    }
+
+    /// Calculates magnitudes of fast fourier transform of a single windowed frame
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream in configured sample rate
+    /// * frame - index of the frame in the stream
+    /// * scratch - buffers of the current thread, magnitudes of frequency bins
+    ///   from 0 to half of window size inclusive are written in to it
+    ///
+    fn frame_magnitudes(&self, decoded_stream: &[f32], frame: usize, scratch: &mut FrameScratch) {
+        let start = frame * self.config.hop_size;
+        self.fft.process(
+            &decoded_stream[start..start + self.config.window_size],
+            &self.window,
+            &mut scratch.fft,
+            &mut scratch.spectrum,
+        );
+        for (magnitude, bin) in scratch.magnitudes.iter_mut().zip(scratch.spectrum.iter()) {
+            *magnitude = bin.norm();
+        }
     }
 }
 
-/// Find points with max magnitude in each of the bins
-/// 
-fn calculate_fingerprint(arr: &[Complex<f32>]) -> usize {
-    let mut high_scores: Vec<f32> = vec![0.0; FREQ_BINS.len()];
-    let mut record_points: Vec<usize> = vec![0; FREQ_BINS.len()];
+/// Counts full frames fitting in the stream
+///
+/// # Arguments:
+/// * samples - number of samples in the stream
+/// * window_size - number of samples in one frame
+/// * hop_size - number of samples between starts of consecutive frames
+///
+/// # Returns number of frames, trailing samples that do not fill a whole frame are skipped
+///
+fn frames_count(samples: usize, window_size: usize, hop_size: usize) -> usize {
+    if samples < window_size {
+        return 0;
+    }
+    (samples - window_size) / hop_size + 1
+}
 
-    for bin in FREQ_BINS[0]..=FREQ_BINS[FREQ_BINS.len() - 1] {
-        let magnitude = arr[bin].re.hypot(arr[bin].im);
+/// Root mean square of samples
+///
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+}
 
-        let mut bin_idx = 0;
-        while FREQ_BINS[bin_idx] < bin {
-            bin_idx += 1;
-        }
+/// Spectral flatness, the ratio of geometric mean to arithmetic mean of bin powers
+///
+/// # Arguments:
+/// * magnitudes - magnitudes of frequency bins
+///
+/// # Returns value between 0 for a single tone and 1 for a perfectly flat spectrum
+///
+fn spectral_flatness(magnitudes: &[f32]) -> f32 {
+    let (sum, log_sum) = magnitudes
+        .iter()
+        .fold((0.0, 0.0), |(sum, log_sum), magnitude| {
+            let power = magnitude * magnitude + f32::MIN_POSITIVE;
+            (sum + power as f64, log_sum + power.ln())
+        });
+    let count = magnitudes.len() as f32;
+    (log_sum / count).exp() / (sum / count as f64) as f32
+}
 
-        if magnitude > high_scores[bin_idx] {
-            high_scores[bin_idx] = magnitude;
-            record_points[bin_idx] = bin;
-        }
+/// Median magnitude of bins of each band, bands are the same as for peaks calculation
+///
+/// # Arguments:
+/// * magnitudes - magnitudes of frequency bins
+/// * band_edges - edges of bands, each band spans bins above one edge up to the next one
+/// * sorted - buffer band magnitudes are sorted in
+///
+/// # Returns collection of median magnitude for each of the bands
+///
+fn band_medians(magnitudes: &[f32], band_edges: &[usize], sorted: &mut Vec<f32>) -> Vec<f32> {
+    band_edges
+        .windows(2)
+        .map(|edges| {
+            sorted.clear();
+            sorted.extend_from_slice(&magnitudes[edges[0] + 1..=edges[1]]);
+            median(sorted)
+        })
+        .collect()
+}
+
+/// Find points with max magnitude in each of the bins
+///
+/// Bin without any energy is marked with 0
+///
+/// # Returns collection of frequency bin and its magnitude for each of the bins
+///
+fn calculate_peaks(arr: &[f32], band_edges: &[usize]) -> Vec<(usize, f32)> {
+    band_edges
+        .windows(2)
+        .map(|edges| {
+            let mut record = (0, 0.0);
+            for (bin, magnitude) in arr.iter().enumerate().take(edges[1] + 1).skip(edges[0] + 1) {
+                if *magnitude > record.1 {
+                    record = (bin, *magnitude);
+                }
+            }
+            record
+        })
+        .collect()
+}
+
+/// Pairs each peak with peaks from the target zone of following frames
+///
+/// # Arguments:
+/// * peaks - collection of frame index and peaks of that frame, ordered by frame index
+/// * fuzz_factor - factor frequency bins are rounded down to the multiple of
+///
+/// # Returns collection of landmarks ordered by offset
+///
+fn pair_peaks(peaks: &[FramePeaks], fuzz_factor: usize) -> Vec<Landmark> {
+    let mut landmarks = Vec::new();
+    for idx in 0..peaks.len() {
+        pair_anchor(peaks, idx, fuzz_factor, &mut landmarks);
     }
+    landmarks
+}
 
-    encode(&record_points)
+/// Pairs peaks of a single anchor frame with peaks from its target zone
+///
+/// # Arguments:
+/// * peaks - collection of frame index and peaks of that frame, ordered by frame index
+/// * idx - position of the anchor frame in peaks collection
+/// * fuzz_factor - factor frequency bins are rounded down to the multiple of
+/// * landmarks - collection the landmarks are pushed to
+///
+fn pair_anchor(
+    peaks: &[FramePeaks],
+    idx: usize,
+    fuzz_factor: usize,
+    landmarks: &mut Vec<Landmark>,
+) {
+    let (anchor_frame, anchor_points) = &peaks[idx];
+    let targets: Vec<(usize, usize)> = peaks[idx + 1..]
+        .iter()
+        .take_while(|(frame, _)| frame - anchor_frame <= TARGET_ZONE)
+        .flat_map(|(frame, points)| {
+            points
+                .iter()
+                .map(move |point| (frame - anchor_frame, point.bin))
+        })
+        .take(FAN_OUT)
+        .collect();
+    for anchor in anchor_points.iter() {
+        for (delta, target) in targets.iter() {
+            // bins lie in configured band range, which validation keeps below MAX_BIN,
+            // also for speed variants, and delta is limited by target zone, so hash always fits
+            if let Some(hash) = encode(anchor.bin, *target, *delta, fuzz_factor) {
+                landmarks.push(Landmark {
+                    hash,
+                    offset: *anchor_frame,
+                });
+            }
+        }
+    }
 }
 
-/// Encodeing function with reverse order
-/// 
-fn encode(arr: &[usize]) -> usize {
-    (arr[4] - (arr[4] % FUZZ_FACTOR)) * usize::pow(10, 10)
-        + (arr[3] - (arr[3] % FUZZ_FACTOR)) * usize::pow(10, 8)
-        + (arr[2] - (arr[2] % FUZZ_FACTOR)) * usize::pow(10, 5)
-        + (arr[1] - (arr[1] % FUZZ_FACTOR)) * usize::pow(10, 2)
-        + (arr[0] - (arr[0] % FUZZ_FACTOR))
+/// Encoding function packing anchor and target frequency bin with frames delta
+///
+/// Frequency bins are reduced by fuzz factor, see `Hash` for the layout
+///
+fn encode(anchor: usize, target: usize, delta: usize, fuzz_factor: usize) -> Option<Hash> {
+    Hash::new(
+        anchor - (anchor % fuzz_factor),
+        target - (target % fuzz_factor),
+        delta,
+    )
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{AudioBuffer, Complex, RealFft};
+    use crate::helpers::{Preprocessor, SignalGenerator};
     use rand::prelude::*;
+    use std::collections::{HashMap, HashSet};
+    use std::f32::consts::PI;
+
+    fn tone_sequence(seed: u64, tones: usize, sample_rate: u32) -> AudioBuffer {
+        SignalGenerator::new(seed, sample_rate).tone_sequence(tones, 0.1, 0.3)
+    }
+
     #[test]
     // #[ignore]
     fn test_hash() {
-        let record_points_0 = vec![32, 45, 100, 140, 235, 300];
-        let record_points_1 = vec![33, 45, 100, 145, 235, 300];
-        assert_eq!(super::encode(&record_points_0), 2354010004432);
-        assert_ne!(super::encode(&record_points_0), super::encode(&record_points_1));
+        assert_eq!(super::encode(32, 45, 1, 2).unwrap().fields(), (32, 44, 1));
+        assert_eq!(super::encode(32, 45, 1, 2), super::encode(33, 45, 1, 2));
+        assert_ne!(super::encode(32, 45, 1, 2), super::encode(33, 45, 1, 1));
+        assert_ne!(super::encode(32, 45, 1, 2), super::encode(32, 45, 2, 2));
+        assert_ne!(super::encode(32, 45, 1, 2), super::encode(45, 32, 1, 2));
+    }
+    #[test]
+    fn test_pair_peaks() {
+        let peak = |frame, bin| super::Peak {
+            frame,
+            time: 0.0,
+            band: 0,
+            bin,
+            frequency: 0.0,
+            magnitude: 1.0,
+        };
+        let peaks = vec![
+            (0, vec![peak(0, 32), peak(0, 40)]),
+            (1, vec![peak(1, 32)]),
+            (5, vec![peak(5, 34)]),
+        ];
+        let landmarks = super::pair_peaks(&peaks, 2);
+        assert_eq!(landmarks.len(), 2);
+        assert_eq!(landmarks[0].offset, 0);
+        assert_eq!(Some(landmarks[0].hash), super::encode(32, 32, 1, 2));
+        assert_eq!(Some(landmarks[1].hash), super::encode(40, 32, 1, 2));
+    }
+    #[test]
+    fn test_frames_count() {
+        assert_eq!(super::frames_count(1000, 1024, 512), 0);
+        assert_eq!(super::frames_count(1024, 1024, 512), 1);
+        assert_eq!(super::frames_count(2047, 1024, 512), 2);
+        assert_eq!(super::frames_count(2048, 1024, 512), 3);
+        assert_eq!(super::frames_count(4096, 1024, 1024), 4);
+    }
+    #[test]
+    fn test_overlapping_frames_shift() {
+        // peak bucket never runs short, so frames only depend on noise floor of preceding frames
+        let config = super::FingerprintConfig {
+            hop_size: 256,
+            max_peaks_per_second: 1000,
+            ..super::FingerprintConfig::default()
+        };
+        let handle = super::FingerprintHandle::new(config).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut stream: Vec<f32> = Vec::new();
+        for _ in 0..120 {
+            let freq = rng.gen_range(200.0, 4000.0);
+            stream.extend((0..300).map(|t| (2.0 * PI * freq * t as f32 / 11025.0).sin() * 10000.0));
+        }
+        let landmarks = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(stream.clone(), 11025))
+            .unwrap();
+        let shifted: Vec<super::Landmark> = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(stream[256..].to_vec(), 11025))
+            .unwrap()
+            .into_iter()
+            .map(|landmark| super::Landmark {
+                offset: landmark.offset + 1,
+                ..landmark
+            })
+            .filter(|landmark| landmark.offset > super::picker::NOISE_FLOOR_FRAMES)
+            .collect();
+        let expected: Vec<super::Landmark> = landmarks
+            .into_iter()
+            .filter(|landmark| landmark.offset > super::picker::NOISE_FLOOR_FRAMES)
+            .collect();
+        assert!(!shifted.is_empty());
+        assert_eq!(shifted, expected);
+    }
+    #[test]
+    fn test_collection_is_ordered_and_reproducible() {
+        let handle = super::FingerprintHandle::new(super::FingerprintConfig::default()).unwrap();
+        let mut rng = StdRng::seed_from_u64(11);
+        let stream = AudioBuffer::new(
+            (0..100_000)
+                .map(|_| rng.gen::<f32>() * 20000.0 - 10000.0)
+                .collect(),
+            11025,
+        );
+        let landmarks = handle.calc_fingerprint_collection(&stream).unwrap();
+        assert!(landmarks
+            .windows(2)
+            .all(|pair| pair[0].offset <= pair[1].offset));
+        for _ in 0..5 {
+            assert_eq!(
+                handle.calc_fingerprint_collection(&stream).unwrap(),
+                landmarks
+            );
+        }
+    }
+    #[test]
+    fn test_sample_rate_independence() {
+        let handle = super::FingerprintHandle::new(super::FingerprintConfig::default()).unwrap();
+        let canonical: HashSet<super::Landmark> = handle
+            .calc_fingerprint_collection(&tone_sequence(3, 30, 11025))
+            .unwrap()
+            .into_iter()
+            .collect();
+        for sample_rate in [22050, 44100, 48000].iter() {
+            let landmarks = handle
+                .calc_fingerprint_collection(&tone_sequence(3, 30, *sample_rate))
+                .unwrap();
+            let common = landmarks
+                .iter()
+                .filter(|landmark| canonical.contains(landmark))
+                .count();
+            assert!(common * 10 > landmarks.len() * 7);
+        }
+        assert!(handle
+            .calc_fingerprint_collection(&AudioBuffer::new(vec![0.0; 4096], 0))
+            .is_err());
+    }
+    #[test]
+    fn test_sample_format_independence() {
+        let handle = super::FingerprintHandle::new(super::FingerprintConfig::default()).unwrap();
+        let mono: Vec<i16> = tone_sequence(6, 20, 22050)
+            .samples
+            .iter()
+            .map(|sample| sample.round() as i16)
+            .collect();
+        let stereo: Vec<i16> = mono.iter().flat_map(|sample| vec![*sample; 2]).collect();
+        let expected = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(
+                mono.iter().map(|sample| f32::from(*sample)).collect(),
+                22050,
+            ))
+            .unwrap();
+        assert!(!expected.is_empty());
+        let floats: Vec<f32> = stereo
+            .iter()
+            .map(|sample| *sample as f32 / 32768.0)
+            .collect();
+        let doubles: Vec<f64> = mono.iter().map(|sample| *sample as f64 / 32768.0).collect();
+        let wide: Vec<i32> = stereo
+            .iter()
+            .map(|sample| i32::from(*sample) << 16)
+            .collect();
+        assert_eq!(
+            handle
+                .calc_pcm_fingerprint_collection(&stereo, 2, 22050)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            handle
+                .calc_pcm_fingerprint_collection(&floats, 2, 22050)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            handle
+                .calc_pcm_fingerprint_collection(&doubles, 1, 22050)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            handle
+                .calc_pcm_fingerprint_collection(&wide, 2, 22050)
+                .unwrap(),
+            expected
+        );
+        assert!(handle
+            .calc_pcm_fingerprint_collection(&stereo[1..], 2, 22050)
+            .is_err());
+        // non-finite floats do not panic
+        let mut broken = floats.clone();
+        broken[1000..1100]
+            .iter_mut()
+            .for_each(|sample| *sample = f32::NAN);
+        broken[5000] = f32::INFINITY;
+        assert!(handle
+            .calc_pcm_fingerprint_collection(&broken, 2, 22050)
+            .is_ok());
+    }
+    #[test]
+    fn test_speed_variants() {
+        let handle = super::FingerprintHandle::new(super::FingerprintConfig::default()).unwrap();
+        let song = tone_sequence(8, 60, 11025);
+        let indexed = handle.calc_fingerprint_collection(&song).unwrap();
+        // the same samples played 3% faster
+        let sped_up = AudioBuffer::new(song.samples[20000..].to_vec(), (11025.0 * 1.03) as u32);
+        let variants = handle
+            .calc_speed_variants(&sped_up, &super::SpeedSearch::default())
+            .unwrap();
+        let scores: Vec<(f32, usize)> = variants
+            .iter()
+            .map(|variant| {
+                let mut votes = super::super::helpers::OffsetVotes::new();
+                for sample in variant.landmarks.iter() {
+                    for song in indexed.iter().filter(|song| song.hash == sample.hash) {
+                        votes.vote("song", song.offset, sample.offset);
+                    }
+                }
+                let score = votes.scores().get("song").cloned().unwrap_or(0);
+                (variant.speed, score)
+            })
+            .collect();
+        let (speed, score) = scores
+            .iter()
+            .max_by_key(|(_, score)| *score)
+            .cloned()
+            .unwrap();
+        assert!((speed - 1.03).abs() < 0.006);
+        assert!(score > scores[0].1 * 2);
+        assert!(handle
+            .calc_speed_variants(
+                &sped_up,
+                &super::SpeedSearch {
+                    max_deviation: 1.5,
+                    step: 0.01
+                }
+            )
+            .is_err());
+    }
+    #[test]
+    fn test_speed_variants_at_top_band_edge() {
+        let config = super::FingerprintConfig {
+            window_size: 4096,
+            hop_size: 2048,
+            bands: super::BandLayout::Bins(vec![200, 400, 600, 800, super::hash::MAX_BIN - 1]),
+            ..super::FingerprintConfig::default()
+        };
+        let handle = super::FingerprintHandle::new(config.clone()).unwrap();
+        let bin_frequency = config.sample_rate as f32 / config.window_size as f32;
+        let segment = config.sample_rate as usize / 2;
+        let mut rng = StdRng::seed_from_u64(16);
+        let mut samples = Vec::new();
+        for _ in 0..30 {
+            // a tone in each band, and a louder one above the top edge, out of the bands of the song
+            let mut bins: Vec<(f32, f32)> = (0..5)
+                .map(|band| ((band * 200 + rng.gen_range(20, 180)) as f32, 3000.0))
+                .collect();
+            bins.push((rng.gen_range(1030, 1060) as f32, 6000.0));
+            samples.extend((0..segment).map(|t| {
+                bins.iter()
+                    .map(|(bin, amplitude)| {
+                        let frequency = bin * bin_frequency;
+                        (2.0 * PI * frequency * t as f32 / config.sample_rate as f32).sin()
+                            * amplitude
+                    })
+                    .sum::<f32>()
+            }));
+        }
+        let song = AudioBuffer::new(samples, config.sample_rate);
+        let indexed = handle.calc_fingerprint_collection(&song).unwrap();
+        // the same samples played 4% slower, tones above the top edge of the song fall in its top band
+        let slowed = AudioBuffer::new(
+            song.samples[segment..].to_vec(),
+            (config.sample_rate as f32 * 0.96) as u32,
+        );
+        let peaks = handle.calc_peaks(&slowed).unwrap();
+        let mut frames: Vec<(usize, usize)> = Vec::new();
+        for peak in peaks.iter() {
+            let bin = (peak.bin as f32 / 0.96).round() as usize;
+            if bin >= super::hash::MAX_BIN {
+                continue;
+            }
+            match frames.last_mut() {
+                Some((frame, count)) if *frame == peak.frame => *count += 1,
+                _ => frames.push((peak.frame, 1)),
+            }
+        }
+        assert!(frames.iter().map(|(_, count)| count).sum::<usize>() < peaks.len());
+        // every anchor peak scaled in to band range is paired with all of its targets
+        let pairs: usize = frames
+            .iter()
+            .enumerate()
+            .map(|(idx, (anchor_frame, anchors))| {
+                let targets: usize = frames[idx + 1..]
+                    .iter()
+                    .take_while(|(frame, _)| frame - anchor_frame <= super::TARGET_ZONE)
+                    .map(|(_, count)| count)
+                    .sum();
+                anchors * targets.min(super::FAN_OUT)
+            })
+            .sum();
+        let variants = handle
+            .calc_speed_variants(
+                &slowed,
+                &super::SpeedSearch {
+                    max_deviation: 0.04,
+                    step: 0.04,
+                },
+            )
+            .unwrap();
+        let scores: Vec<(f32, usize)> = variants
+            .iter()
+            .map(|variant| {
+                let mut votes = super::super::helpers::OffsetVotes::new();
+                for sample in variant.landmarks.iter() {
+                    for song in indexed.iter().filter(|song| song.hash == sample.hash) {
+                        votes.vote("song", song.offset, sample.offset);
+                    }
+                }
+                (
+                    variant.speed,
+                    votes.scores().get("song").cloned().unwrap_or(0),
+                )
+            })
+            .collect();
+        let (speed, score) = scores
+            .iter()
+            .max_by_key(|(_, score)| *score)
+            .cloned()
+            .unwrap();
+        assert!((speed - 0.96).abs() < 0.001);
+        assert!(score > 20);
+        let slowest = variants
+            .iter()
+            .find(|variant| (variant.speed - 0.96).abs() < 0.001)
+            .unwrap();
+        assert_eq!(slowest.landmarks.len(), pairs);
+    }
+    #[test]
+    fn test_silence_gate() {
+        let config = super::FingerprintConfig::default();
+        let handle = super::FingerprintHandle::new(config.clone()).unwrap();
+        let mut rng = StdRng::seed_from_u64(13);
+        let mut stream = tone_sequence(5, 20, config.sample_rate);
+        let gap = stream.samples.len()..stream.samples.len() + 20 * config.window_size;
+        stream
+            .samples
+            .extend(gap.clone().map(|_| rng.gen::<f32>() * 20.0 - 10.0)); // near silent hiss
+        stream
+            .samples
+            .extend(tone_sequence(6, 20, config.sample_rate).samples);
+        let fingerprints = handle.calc_fingerprints(&stream).unwrap();
+        let gap_frames = (gap.start / config.hop_size + 1)..(gap.end / config.hop_size - 1);
+        assert!(fingerprints.silent_frames >= gap_frames.len());
+        assert!(fingerprints.silent_frames < fingerprints.frames / 2);
+        assert!(!fingerprints.landmarks.is_empty());
+        assert!(fingerprints
+            .landmarks
+            .iter()
+            .all(|landmark| !gap_frames.contains(&landmark.offset)));
+        assert!(handle
+            .calc_peaks(&stream)
+            .unwrap()
+            .iter()
+            .all(|peak| !gap_frames.contains(&peak.frame)));
+
+        let silence = handle
+            .calc_fingerprints(&AudioBuffer::new(vec![0.0; 8192], config.sample_rate))
+            .unwrap();
+        assert_eq!(silence.frames, silence.silent_frames);
+        assert!(silence.landmarks.is_empty());
+    }
+    #[test]
+    fn test_configured_preprocessing() {
+        let loud = SignalGenerator::new(8, 22050).song(6.0, 0.5);
+        let quiet = AudioBuffer::new(
+            loud.samples.iter().map(|sample| sample / 300.0).collect(),
+            loud.sample_rate,
+        );
+        let plain = super::FingerprintHandle::new(super::FingerprintConfig::default()).unwrap();
+        let expected = plain.calc_fingerprint_collection(&loud).unwrap();
+        assert!(plain.calc_fingerprint_collection(&quiet).unwrap().len() < expected.len() / 2);
+
+        let config = super::FingerprintConfig {
+            preprocessor: Some(Preprocessor::default()),
+            ..super::FingerprintConfig::default()
+        };
+        assert_ne!(config.to_string(), plain.config().to_string());
+        let handle = super::FingerprintHandle::new(config).unwrap();
+        let loud: HashSet<_> = handle
+            .calc_fingerprint_collection(&loud)
+            .unwrap()
+            .into_iter()
+            .collect();
+        let quiet = handle.calc_fingerprint_collection(&quiet).unwrap();
+        assert!(!quiet.is_empty());
+        let common = quiet
+            .iter()
+            .filter(|landmark| loud.contains(landmark))
+            .count();
+        assert!(common * 10 > quiet.len() * 9);
+    }
+    #[test]
+    fn test_adaptive_peaks() {
+        let config = super::FingerprintConfig::default();
+        let handle = super::FingerprintHandle::new(config.clone()).unwrap();
+        let mut rng = StdRng::seed_from_u64(17);
+        let seconds = 5;
+        let stream = AudioBuffer::new(
+            (0..config.sample_rate as usize * seconds)
+                .map(|t| {
+                    let tone = (2.0 * PI * 1000.0 * t as f32 / config.sample_rate as f32).sin();
+                    tone * 3000.0 + rng.gen::<f32>() * 6000.0 - 3000.0
+                })
+                .collect(),
+            config.sample_rate,
+        );
+        let peaks = handle.calc_peaks(&stream).unwrap();
+        assert!(!peaks.is_empty());
+        let bands = config.band_edges().len() - 1;
+        assert!(peaks.len() <= config.max_peaks_per_second * seconds + bands);
+        let tone_peaks = peaks
+            .iter()
+            .filter(|peak| (peak.frequency - 1000.0).abs() < 25.0)
+            .count();
+        assert!(tone_peaks * 10 > peaks.len() * 7);
     }
     #[test]
-    fn test_calculate_fingerprint() {
+    fn test_spectral_flatness() {
+        let fft = RealFft::new(1024);
+        let mut scratch = fft.scratch();
+        let mut spectrum = vec![Complex::from(0.0); 513];
+        let magnitudes = |spectrum: &[Complex<f32>]| -> Vec<f32> {
+            spectrum.iter().map(|bin| bin.norm()).collect()
+        };
+        let mut impulse = vec![0.0; 1024];
+        impulse[0] = 1000.0;
+        fft.process(&impulse, &[1.0; 1024], &mut scratch, &mut spectrum);
+        assert!(super::spectral_flatness(&magnitudes(&spectrum)) > 0.99);
+        let tone: Vec<f32> = (0..1024)
+            .map(|t| (2.0 * PI * 100.0 * t as f32 / 1024.0).sin())
+            .collect();
+        fft.process(&tone, &[1.0; 1024], &mut scratch, &mut spectrum);
+        assert!(super::spectral_flatness(&magnitudes(&spectrum)) < 0.01);
+    }
+    #[test]
+    fn test_band_layout_independent_of_window() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut samples = Vec::new();
+        for _ in 0..10 {
+            let freq = rng.gen_range(400.0, 3000.0);
+            samples
+                .extend((0..5512).map(|t| (2.0 * PI * freq * t as f32 / 11025.0).sin() * 10000.0));
+        }
+        let stream = AudioBuffer::new(samples, 11025);
+        let strongest_bands = |window_size: usize| -> HashMap<usize, usize> {
+            let config = super::FingerprintConfig {
+                window_size,
+                bands: super::BandLayout::Mel {
+                    low: 300.0,
+                    high: 4000.0,
+                    bands: 8,
+                },
+                ..super::FingerprintConfig::default()
+            };
+            let handle = super::FingerprintHandle::new(config).unwrap();
+            let mut strongest: HashMap<usize, (usize, f32)> = HashMap::new();
+            for peak in handle.calc_peaks(&stream).unwrap() {
+                let entry = strongest
+                    .entry(peak.frame)
+                    .or_insert((peak.band, peak.magnitude));
+                if peak.magnitude > entry.1 {
+                    *entry = (peak.band, peak.magnitude);
+                }
+            }
+            strongest
+                .into_iter()
+                .map(|(frame, (band, _))| (frame, band))
+                .collect()
+        };
+        let short = strongest_bands(1024);
+        let long = strongest_bands(2048);
+        let common = long
+            .iter()
+            .filter(|(frame, band)| short.get(frame) == Some(band))
+            .count();
+        assert!(common * 10 > long.len() * 8);
+    }
+    #[test]
+    fn test_calculate_peaks() {
+        let config = super::FingerprintConfig::default();
+        let band_edges = config.band_edges();
         let mut rng = rand::thread_rng();
-        let mut arr_f32: Vec<f32> = vec![0.0; super::FFT_WINDOW_SIZE];
+        let mut arr_f32: Vec<f32> = vec![0.0; config.window_size];
         arr_f32.iter_mut().for_each(|complex_num| {
             *complex_num = rng.gen::<f32>() * 10000_f32;
         });
-        let arr: Vec<super::Complex<f32>> = arr_f32.iter().map(super::Complex::from).collect();
-        let fingerprint: usize = super::calculate_fingerprint(&arr);
-        let fingerprint_log10 = (fingerprint as f64).log10();
-        assert_eq!(
-            fingerprint_log10 > 12_f64 && fingerprint_log10 < 13_f64,
-            true
+        let peaks = super::calculate_peaks(&arr_f32, &band_edges);
+        assert_eq!(peaks.len(), band_edges.len() - 1);
+        for (idx, (bin, magnitude)) in peaks.iter().enumerate() {
+            assert!(*bin > band_edges[idx]);
+            assert!(*bin <= band_edges[idx + 1]);
+            assert_eq!(arr_f32[*bin], *magnitude);
+            assert!(arr_f32[band_edges[idx] + 1..=band_edges[idx + 1]]
+                .iter()
+                .all(|other| other <= magnitude));
+        }
+    }
+    #[test]
+    fn test_calc_peaks() {
+        let config = super::FingerprintConfig::default();
+        let band_edges = config.band_edges();
+        let handle = super::FingerprintHandle::new(config.clone()).unwrap();
+        let bin_width = config.sample_rate as f32 / config.window_size as f32;
+        let freq = 1000.0;
+        let stream = AudioBuffer::new(
+            (0..config.sample_rate)
+                .map(|t| (2.0 * PI * freq * t as f32 / config.sample_rate as f32).sin() * 10000.0)
+                .collect(),
+            config.sample_rate,
+        );
+        let peaks = handle.calc_peaks(&stream).unwrap();
+        let frames = super::frames_count(
+            config.sample_rate as usize,
+            config.window_size,
+            config.hop_size,
         );
+        let strongest: Vec<&super::Peak> = peaks
+            .iter()
+            .filter(|peak| peak.magnitude > 100_000.0)
+            .collect();
+        assert_eq!(strongest.len(), frames);
+        for (frame, peak) in strongest.iter().enumerate() {
+            assert_eq!(peak.frame, frame);
+            assert!((peak.frequency - freq).abs() <= bin_width);
+            assert!((peak.frequency - peak.bin as f32 * bin_width).abs() < 1e-3);
+            let time = (frame * config.hop_size) as f32 / config.sample_rate as f32;
+            assert!((peak.time - time).abs() < 1e-6);
+            assert!(peak.bin > band_edges[peak.band]);
+            assert!(peak.bin <= band_edges[peak.band + 1]);
+        }
     }
 }
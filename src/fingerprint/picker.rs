@@ -0,0 +1,175 @@
+use super::{FingerprintConfig, Peak};
+use std::collections::VecDeque;
+
+pub const NOISE_FLOOR_FRAMES: usize = 32; // number of recent frames the noise floor median is taken over, about 1.5 second for default configuration
+
+/// Selects peaks standing out of the local noise floor
+///
+/// Noise floor of each band is a running median over recent frames
+/// of the median magnitude of band bins, so it follows slow changes of background noise
+/// while short tonal events do not lift it.
+/// Only peaks exceeding the noise floor by configured margin are accepted,
+/// and number of peaks is limited by a token bucket refilled at configured peaks per second,
+/// keeping peaks with the highest ratio to the noise floor when the bucket runs short.
+/// Frames are picked in order, so the result only depends on preceding frames
+/// and is the same for whole stream and streaming processing.
+///
+pub struct PeakPicker {
+    /// Band medians of recent frames, the oldest first
+    history: VecDeque<Vec<f32>>,
+    /// Min ratio of peak magnitude to noise floor
+    margin: f32,
+    /// Number of peaks the bucket is refilled with each frame
+    refill: f32,
+    /// Max number of peaks in the bucket
+    capacity: f32,
+    /// Number of peaks that can be accepted now
+    credit: f32,
+}
+
+impl PeakPicker {
+    /// Create new instance of PeakPicker
+    ///
+    /// # Arguments:
+    /// * config - parameters of fingerprint calculation
+    ///
+    /// # Returns new instance of PeakPicker with empty noise history and full bucket
+    ///
+    pub fn new(config: &FingerprintConfig) -> Self {
+        let capacity = (config.band_edges().len() - 1) as f32;
+        Self {
+            history: VecDeque::with_capacity(NOISE_FLOOR_FRAMES + 1),
+            margin: 10_f32.powf(config.peak_margin / 20.0),
+            refill: config.max_peaks_per_second as f32 * config.hop_size as f32
+                / config.sample_rate as f32,
+            capacity,
+            credit: capacity,
+        }
+    }
+
+    /// Picks peaks of the next frame
+    ///
+    /// # Arguments:
+    /// * peaks - loudest bin of each band of the frame
+    /// * medians - median magnitude of bins of each band of the frame
+    ///
+    /// # Returns accepted peaks ordered by band
+    ///
+    pub fn pick(&mut self, peaks: Vec<Peak>, medians: Vec<f32>) -> Vec<Peak> {
+        self.history.push_back(medians);
+        if self.history.len() > NOISE_FLOOR_FRAMES {
+            self.history.pop_front();
+        }
+        let mut accepted: Vec<(Peak, f32)> = peaks
+            .into_iter()
+            .filter_map(|peak| {
+                let floor = self.noise_floor(peak.band);
+                let ratio = if floor > 0.0 {
+                    peak.magnitude / floor
+                } else {
+                    f32::INFINITY
+                };
+                if ratio >= self.margin && peak.magnitude > 0.0 {
+                    Some((peak, ratio))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        self.credit = (self.credit + self.refill).min(self.capacity);
+        let allowed = self.credit.floor() as usize;
+        if accepted.len() > allowed {
+            accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+            accepted.truncate(allowed);
+            accepted.sort_by_key(|(peak, _)| peak.band);
+        }
+        self.credit -= accepted.len() as f32;
+        accepted.into_iter().map(|(peak, _)| peak).collect()
+    }
+
+    /// Median over recent frames of band medians
+    ///
+    fn noise_floor(&self, band: usize) -> f32 {
+        let mut medians: Vec<f32> = self.history.iter().map(|frame| frame[band]).collect();
+        median(&mut medians)
+    }
+}
+
+/// Median of values, for even number of values the lower one of the middle pair
+///
+/// Values are reordered in place, NaN values are ordered above all numbers, so they never panic
+///
+pub fn median(values: &mut [f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let middle = (values.len() - 1) / 2;
+    *values
+        .select_nth_unstable_by(middle, |a, b| a.total_cmp(b))
+        .1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{median, PeakPicker, NOISE_FLOOR_FRAMES};
+    use crate::fingerprint::{FingerprintConfig, Peak};
+
+    fn peak(band: usize, magnitude: f32) -> Peak {
+        Peak {
+            frame: 0,
+            time: 0.0,
+            band,
+            bin: 40 + band,
+            frequency: 0.0,
+            magnitude,
+        }
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut []), 0.0);
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [f32::NAN, 1.0, 3.0]), 3.0);
+    }
+    #[test]
+    fn test_margin_over_noise_floor() {
+        let config = FingerprintConfig {
+            peak_margin: 6.0,
+            max_peaks_per_second: 1000,
+            ..FingerprintConfig::default()
+        };
+        let mut picker = PeakPicker::new(&config);
+        for _ in 0..NOISE_FLOOR_FRAMES {
+            picker.pick(vec![], vec![10.0, 10.0]);
+        }
+        let picked = picker.pick(vec![peak(0, 15.0), peak(1, 25.0)], vec![10.0, 10.0]);
+        assert_eq!(picked, vec![peak(1, 25.0)]);
+        // a single loud frame does not lift the noise floor
+        picker.pick(vec![], vec![1000.0, 1000.0]);
+        let picked = picker.pick(vec![peak(0, 21.0)], vec![10.0, 10.0]);
+        assert_eq!(picked, vec![peak(0, 21.0)]);
+    }
+    #[test]
+    fn test_peaks_per_second() {
+        let config = FingerprintConfig {
+            peak_margin: 0.0,
+            max_peaks_per_second: 43,
+            ..FingerprintConfig::default()
+        };
+        let frames_per_second = config.sample_rate as usize / config.hop_size;
+        let bands = config.band_edges().len() - 1;
+        let mut picker = PeakPicker::new(&config);
+        let mut picked = Vec::new();
+        for _ in 0..frames_per_second * 10 {
+            let peaks = (0..bands)
+                .map(|band| peak(band, 10.0 + band as f32))
+                .collect();
+            picked.extend(picker.pick(peaks, vec![1.0; bands]));
+        }
+        assert!(picked.len() <= 43 * 10 + bands);
+        assert!(picked.len() >= 40 * 10);
+        // once the first full bucket is spent, only the strongest peaks are kept
+        assert!(picked.iter().skip(bands).all(|peak| peak.band >= 3));
+    }
+}
@@ -0,0 +1,128 @@
+use rustfft::algorithm::Radix4;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::FFT;
+use std::f32::consts::PI;
+
+/// Fast fourier transform of real input
+///
+/// Real frame of N samples is packed in to N/2 complex numbers, even samples as real parts
+/// and odd samples as imaginary parts, transformed by complex FFT of half the size,
+/// then the spectrum of even and odd samples is separated and combined with twiddle factors.
+/// That takes about half of the work of complex FFT of the whole frame,
+/// and only non negative frequency bins are calculated, as the rest mirrors them.
+///
+pub struct RealFft {
+    /// Complex FFT of half of the frame size
+    fft: Radix4<f32>,
+    /// Twiddle factors `exp(-2 pi i k / N)` for bins from 0 to N/2
+    twiddles: Vec<Complex<f32>>,
+}
+
+/// Buffers of a single transform, reused between frames processed on the same thread
+///
+pub struct FftScratch {
+    /// Packed input of half complex FFT
+    packed: Vec<Complex<f32>>,
+    /// Output of half complex FFT
+    transformed: Vec<Complex<f32>>,
+}
+
+impl RealFft {
+    /// Create new instance of RealFft
+    ///
+    /// # Arguments:
+    /// * size - number of real samples in the frame, power of two not lower than 4
+    ///
+    /// # Returns new instance of RealFft
+    ///
+    pub fn new(size: usize) -> Self {
+        let half = size / 2;
+        Self {
+            fft: Radix4::new(half, false),
+            twiddles: (0..=half)
+                .map(|k| Complex::from_polar(&1.0, &(-2.0 * PI * k as f32 / size as f32)))
+                .collect(),
+        }
+    }
+
+    /// Creates buffers for transforms of this size
+    ///
+    pub fn scratch(&self) -> FftScratch {
+        let half = self.twiddles.len() - 1;
+        FftScratch {
+            packed: vec![Complex::zero(); half],
+            transformed: vec![Complex::zero(); half],
+        }
+    }
+
+    /// Transforms frame multiplied by window function
+    ///
+    /// # Arguments:
+    /// * samples - frame samples
+    /// * window - window function coefficients, the same length as frame
+    /// * scratch - buffers created by `scratch` of this transform
+    /// * spectrum - output for frequency bins from 0 to N/2 inclusive
+    ///
+    pub fn process(
+        &self,
+        samples: &[f32],
+        window: &[f32],
+        scratch: &mut FftScratch,
+        spectrum: &mut [Complex<f32>],
+    ) {
+        let half = scratch.packed.len();
+        for (n, packed) in scratch.packed.iter_mut().enumerate() {
+            *packed = Complex::new(
+                samples[2 * n] * window[2 * n],
+                samples[2 * n + 1] * window[2 * n + 1],
+            );
+        }
+        self.fft
+            .process(&mut scratch.packed, &mut scratch.transformed);
+        let transformed = &scratch.transformed;
+        for (k, bin) in spectrum.iter_mut().enumerate().take(half + 1) {
+            let z = transformed[k % half];
+            let mirrored = transformed[(half - k) % half].conj();
+            let even = (z + mirrored) * 0.5;
+            let odd = (z - mirrored) * Complex::new(0.0, -0.5);
+            *bin = even + self.twiddles[k] * odd;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RealFft;
+    use rand::prelude::*;
+    use rustfft::algorithm::Radix4;
+    use rustfft::num_complex::Complex;
+    use rustfft::num_traits::Zero;
+    use rustfft::FFT;
+
+    #[test]
+    fn test_equals_complex_fft() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for size in [8, 64, 1024, 4096].iter() {
+            let samples: Vec<f32> = (0..*size).map(|_| rng.gen::<f32>() * 2.0 - 1.0).collect();
+            let window: Vec<f32> = (0..*size).map(|_| rng.gen::<f32>()).collect();
+            let mut input: Vec<Complex<f32>> = samples
+                .iter()
+                .zip(window.iter())
+                .map(|(sample, coefficient)| Complex::from(sample * coefficient))
+                .collect();
+            let mut expected = vec![Complex::zero(); *size];
+            Radix4::new(*size, false).process(&mut input, &mut expected);
+
+            let fft = RealFft::new(*size);
+            let mut scratch = fft.scratch();
+            let mut spectrum = vec![Complex::zero(); size / 2 + 1];
+            for _ in 0..2 {
+                fft.process(&samples, &window, &mut scratch, &mut spectrum);
+                for (bin, expected) in spectrum.iter().zip(expected.iter()) {
+                    assert!((bin - expected).norm() < 1e-3 * (*size as f32).sqrt());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::Landmark;
+use std::error::Error;
+
+const MAX_DEVIATION: f32 = 0.05; // radio stations speed tracks up by a few percent
+const STEP: f32 = 0.005; // shifts bin 200 by a single bin, finer than fuzz factor tolerates
+
+/// Range of playback speed factors searched by speed tolerant matching
+///
+/// Speed factor is the playback speed of the sample relative to the indexed song,
+/// so 1.03 means the sample is played 3% faster, and its frequencies are 3% higher.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeedSearch {
+    /// Max relative deviation of speed from 1.0 in both directions
+    pub max_deviation: f32,
+    /// Difference between consecutive speed factors
+    pub step: f32,
+}
+
+impl Default for SpeedSearch {
+    fn default() -> Self {
+        Self {
+            max_deviation: MAX_DEVIATION,
+            step: STEP,
+        }
+    }
+}
+
+impl SpeedSearch {
+    /// Checks if search range is valid
+    ///
+    /// # Returns success if speed factors can be calculated, dynamic Error otherwise
+    ///
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !(self.max_deviation >= 0.0 && self.max_deviation < 1.0) {
+            return Err(Box::from(format!(
+                "Max speed deviation must be between 0 and 1, got {}",
+                self.max_deviation
+            )));
+        }
+        if self.step <= 0.0 || !self.step.is_finite() {
+            return Err(Box::from(format!(
+                "Speed step must be greater than zero, got {}",
+                self.step
+            )));
+        }
+        Ok(())
+    }
+
+    /// Speed factors to search, starting with 1.0 and moving away from it in both directions
+    ///
+    pub fn factors(&self) -> Vec<f32> {
+        let steps = (self.max_deviation / self.step + 1e-3).floor() as usize;
+        let mut factors = vec![1.0];
+        for step in 1..=steps {
+            let deviation = step as f32 * self.step;
+            factors.push(1.0 + deviation);
+            factors.push(1.0 - deviation);
+        }
+        factors
+    }
+}
+
+/// Landmarks of a sample mapped to the time and frequency scale of the song for a speed factor
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeedVariant {
+    /// Assumed playback speed of the sample relative to the song
+    pub speed: f32,
+    /// Landmarks with frequency bins divided and offsets multiplied by the speed factor
+    pub landmarks: Vec<Landmark>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpeedSearch;
+
+    #[test]
+    fn test_factors() {
+        let search = SpeedSearch {
+            max_deviation: 0.02,
+            step: 0.01,
+        };
+        assert!(search.validate().is_ok());
+        let factors = search.factors();
+        assert_eq!(factors.len(), 5);
+        assert_eq!(factors[0], 1.0);
+        assert!((factors[3] - 1.02).abs() < 1e-6);
+        assert!((factors[4] - 0.98).abs() < 1e-6);
+        assert_eq!(SpeedSearch::default().factors().len(), 21);
+        assert!(SpeedSearch {
+            step: 0.0,
+            ..SpeedSearch::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
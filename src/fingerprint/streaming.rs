@@ -0,0 +1,331 @@
+use super::{
+    pair_anchor, FingerprintConfig, FingerprintHandle, FramePeaks, Landmark, PeakPicker,
+    TARGET_ZONE,
+};
+use crate::helpers::{downmix, Filters, Sample, StreamResampler};
+use std::error::Error;
+
+/// Calculates fingerprints of a stream delivered in chunks of any size
+///
+/// Stream is resampled to configured sample rate by a resampler keeping its kernel history,
+/// filtered by preprocessing filters keeping their state,
+/// samples that do not fill a whole frame are kept until the next push,
+/// and anchor frames wait until their whole target zone is calculated,
+/// so landmarks are exactly the same as if the whole stream was processed at once
+/// by `FingerprintHandle::calc_fingerprint_collection`.
+/// Offsets of landmarks are counted from the first pushed sample.
+/// Loudness normalisation needs the whole stream, so preprocessing with target level
+/// can not be streamed.
+///
+pub struct StreamingFingerprinter {
+    /// Helper calculating frame peaks
+    handle: FingerprintHandle,
+    /// Resampler from sample rate of the stream to configured sample rate
+    resampler: StreamResampler,
+    /// Configured preprocessing filters
+    filters: Option<Filters>,
+    /// Samples starting at the first sample of the next frame
+    buffer: Vec<f32>,
+    /// Peak picker holding noise floor of calculated frames
+    picker: PeakPicker,
+    /// Index of the next frame to calculate
+    next_frame: usize,
+    /// Number of frames skipped by silence gate so far
+    silent_frames: usize,
+    /// Peaks of frames that are still waiting for the whole target zone
+    pending: Vec<FramePeaks>,
+}
+
+impl StreamingFingerprinter {
+    /// Create new instance of StreamingFingerprinter for a stream in configured sample rate
+    ///
+    /// # Arguments:
+    /// * config - parameters of fingerprint calculation
+    ///
+    /// # Returns success of StreamingFingerprinter if configuration is valid, dynamic error otherwise
+    ///
+    pub fn new(config: FingerprintConfig) -> Result<Self, Box<dyn Error>> {
+        let sample_rate = config.sample_rate;
+        Self::with_sample_rate(config, sample_rate)
+    }
+
+    /// Create new instance of StreamingFingerprinter for a stream in any sample rate
+    ///
+    /// # Arguments:
+    /// * config - parameters of fingerprint calculation
+    /// * sample_rate - number of samples per second of the pushed stream
+    ///
+    /// # Returns success of StreamingFingerprinter if configuration and sample rate are valid,
+    /// dynamic error otherwise, also if configured preprocessing normalises loudness
+    ///
+    pub fn with_sample_rate(
+        config: FingerprintConfig,
+        sample_rate: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        if sample_rate == 0 {
+            return Err(Box::from("Invalid sample rate of decoded stream"));
+        }
+        let handle = FingerprintHandle::new(config)?;
+        let filters = match &handle.config().preprocessor {
+            Some(preprocessor) if preprocessor.target_level.is_some() => {
+                return Err(Box::from(
+                    "Loudness normalisation needs the whole stream and can not be streamed",
+                ))
+            }
+            Some(preprocessor) => Some(preprocessor.filters(handle.config().sample_rate)),
+            None => None,
+        };
+        Ok(Self {
+            picker: PeakPicker::new(handle.config()),
+            resampler: StreamResampler::new(sample_rate, handle.config().sample_rate),
+            filters,
+            handle,
+            buffer: Vec::new(),
+            next_frame: 0,
+            silent_frames: 0,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Getter for configuration
+    ///
+    /// # Returns parameters the fingerprints are calculated with
+    ///
+    pub fn config(&self) -> &FingerprintConfig {
+        &self.handle.config
+    }
+
+    /// Number of frames calculated so far
+    ///
+    pub fn frames(&self) -> usize {
+        self.next_frame
+    }
+
+    /// Number of frames skipped by silence gate so far
+    ///
+    pub fn silent_frames(&self) -> usize {
+        self.silent_frames
+    }
+
+    /// Time in seconds from the beginning of the stream to the start of the next frame
+    ///
+    pub fn timestamp(&self) -> f32 {
+        let config = self.config();
+        (self.next_frame * config.hop_size) as f32 / config.sample_rate as f32
+    }
+
+    /// Feeds fingerprinter with the next chunk of the stream
+    ///
+    /// # Arguments:
+    /// * samples - next samples of the stream in its sample rate
+    ///
+    /// # Returns collection of landmarks ordered by offset, which anchor frames are complete
+    ///
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Landmark> {
+        let resampled = self.resampler.push(samples);
+        self.process(resampled)
+    }
+
+    /// Feeds fingerprinter with the next chunk of PCM samples of any format and number of channels
+    ///
+    /// # Arguments:
+    /// * samples - next interleaved samples of the stream in its sample rate,
+    ///   `i16`, `i32`, or `f32` and `f64` in range from -1 to 1
+    /// * channels - number of interleaved channels, each chunk holds whole frames of all of them
+    ///
+    /// # Returns success of landmarks ordered by offset, which anchor frames are complete,
+    /// dynamic error if channels do not fit the samples
+    ///
+    pub fn push_pcm<S: Sample>(
+        &mut self,
+        samples: &[S],
+        channels: usize,
+    ) -> Result<Vec<Landmark>, Box<dyn Error>> {
+        Ok(self.push(&downmix(samples, channels)?))
+    }
+
+    /// Preprocesses samples in configured sample rate and calculates their frames
+    ///
+    fn process(&mut self, mut samples: Vec<f32>) -> Vec<Landmark> {
+        if let Some(filters) = self.filters.as_mut() {
+            filters.filter(&mut samples);
+        }
+        self.buffer.extend(samples);
+        let (peaks, silent_frames) =
+            self.handle
+                .calc_frame_peaks(&self.buffer, self.next_frame, &mut self.picker);
+        let consumed = peaks.len() * self.config().hop_size;
+        self.buffer.drain(..consumed.min(self.buffer.len()));
+        self.next_frame += peaks.len();
+        self.silent_frames += silent_frames;
+        self.pending.extend(peaks);
+
+        let last_frame = match self.pending.last() {
+            Some((frame, _)) => *frame,
+            None => return Vec::new(),
+        };
+        let complete = self
+            .pending
+            .iter()
+            .take_while(|(frame, _)| frame + TARGET_ZONE <= last_frame)
+            .count();
+        let mut landmarks = Vec::new();
+        for idx in 0..complete {
+            pair_anchor(
+                &self.pending,
+                idx,
+                self.config().fuzz_factor,
+                &mut landmarks,
+            );
+        }
+        self.pending.drain(..complete);
+        landmarks
+    }
+
+    /// Ends the stream
+    ///
+    /// Samples that do not fill a whole frame are skipped, the same as for whole stream processing
+    ///
+    /// # Returns collection of landmarks ordered by offset for anchor frames left
+    ///
+    pub fn finish(mut self) -> Vec<Landmark> {
+        let resampled = self.resampler.finish();
+        let mut landmarks = self.process(resampled);
+        for idx in 0..self.pending.len() {
+            pair_anchor(
+                &self.pending,
+                idx,
+                self.config().fuzz_factor,
+                &mut landmarks,
+            );
+        }
+        landmarks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FingerprintConfig, FingerprintHandle, StreamingFingerprinter};
+    use crate::helpers::{AudioBuffer, Preprocessor, SignalGenerator};
+    use rand::prelude::*;
+
+    #[test]
+    fn test_streaming_equals_whole_stream() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut stream: Vec<f32> = (0..60_000)
+            .map(|_| rng.gen::<f32>() * 20000.0 - 10000.0)
+            .collect();
+        stream[20_000..30_000]
+            .iter_mut()
+            .for_each(|sample| *sample = 0.0);
+        let config = FingerprintConfig::default();
+        let expected = FingerprintHandle::new(config.clone())
+            .unwrap()
+            .calc_fingerprints(&AudioBuffer::new(stream.clone(), config.sample_rate))
+            .unwrap();
+
+        let mut fingerprinter = StreamingFingerprinter::new(config.clone()).unwrap();
+        let mut landmarks = Vec::new();
+        let mut position = 0;
+        while position < stream.len() {
+            let end = (position + rng.gen_range(1, 3000)).min(stream.len());
+            landmarks.extend(fingerprinter.push(&stream[position..end]));
+            position = end;
+        }
+        assert_eq!(fingerprinter.frames(), expected.frames);
+        assert_eq!(fingerprinter.silent_frames(), expected.silent_frames);
+        assert!(expected.silent_frames > 0);
+        landmarks.extend(fingerprinter.finish());
+        assert!(!landmarks.is_empty());
+        assert_eq!(landmarks, expected.landmarks);
+
+        // the same stream as interleaved stereo floats in range from -1 to 1
+        let stereo: Vec<f32> = stream
+            .iter()
+            .flat_map(|sample| vec![sample / 32768.0; 2])
+            .collect();
+        let mut fingerprinter = StreamingFingerprinter::new(config).unwrap();
+        assert!(fingerprinter.push_pcm(&stereo[..3], 2).is_err());
+        let mut pcm_landmarks = Vec::new();
+        for chunk in stereo.chunks(2 * 1000) {
+            pcm_landmarks.extend(fingerprinter.push_pcm(chunk, 2).unwrap());
+        }
+        pcm_landmarks.extend(fingerprinter.finish());
+        assert_eq!(pcm_landmarks, expected.landmarks);
+    }
+    #[test]
+    fn test_streaming_resamples_like_whole_stream() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let config = FingerprintConfig::default();
+        let handle = FingerprintHandle::new(config.clone()).unwrap();
+        for sample_rate in [44100, 48000].iter() {
+            let stream = SignalGenerator::new(*sample_rate as u64, *sample_rate).song(4.0, 0.5);
+            let expected = handle.calc_fingerprint_collection(&stream).unwrap();
+            let mut fingerprinter =
+                StreamingFingerprinter::with_sample_rate(config.clone(), *sample_rate).unwrap();
+            let mut landmarks = Vec::new();
+            let mut position = 0;
+            while position < stream.samples.len() {
+                let end = (position + rng.gen_range(1, 5000)).min(stream.samples.len());
+                landmarks.extend(fingerprinter.push(&stream.samples[position..end]));
+                position = end;
+            }
+            landmarks.extend(fingerprinter.finish());
+            assert!(!landmarks.is_empty());
+            assert_eq!(landmarks, expected);
+        }
+        assert!(StreamingFingerprinter::with_sample_rate(config, 0).is_err());
+    }
+    #[test]
+    fn test_streaming_preprocesses_like_whole_stream() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let config = FingerprintConfig {
+            preprocessor: Some(Preprocessor {
+                pre_emphasis: Some(0.97),
+                target_level: None,
+                ..Preprocessor::default()
+            }),
+            ..FingerprintConfig::default()
+        };
+        let mut stream = SignalGenerator::new(7, 22050).song(4.0, 0.5);
+        stream
+            .samples
+            .iter_mut()
+            .for_each(|sample| *sample += 3000.0);
+        let expected = FingerprintHandle::new(config.clone())
+            .unwrap()
+            .calc_fingerprint_collection(&stream)
+            .unwrap();
+        let mut fingerprinter =
+            StreamingFingerprinter::with_sample_rate(config.clone(), stream.sample_rate).unwrap();
+        let mut landmarks = Vec::new();
+        let mut position = 0;
+        while position < stream.samples.len() {
+            let end = (position + rng.gen_range(1, 5000)).min(stream.samples.len());
+            landmarks.extend(fingerprinter.push(&stream.samples[position..end]));
+            position = end;
+        }
+        landmarks.extend(fingerprinter.finish());
+        assert!(!landmarks.is_empty());
+        assert_eq!(landmarks, expected);
+
+        let normalised = FingerprintConfig {
+            preprocessor: Some(Preprocessor::default()),
+            ..config
+        };
+        assert!(StreamingFingerprinter::new(normalised).is_err());
+    }
+    #[test]
+    fn test_frame_counter() {
+        let config = FingerprintConfig::default();
+        let mut fingerprinter = StreamingFingerprinter::new(config.clone()).unwrap();
+        fingerprinter.push(&vec![0.0; config.window_size - 1]);
+        assert_eq!(fingerprinter.frames(), 0);
+        fingerprinter.push(&[0.0]);
+        assert_eq!(fingerprinter.frames(), 1);
+        fingerprinter.push(&vec![0.0; config.hop_size * 3]);
+        assert_eq!(fingerprinter.frames(), 4);
+        let expected = (4 * config.hop_size) as f32 / config.sample_rate as f32;
+        assert!((fingerprinter.timestamp() - expected).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,97 @@
+use super::hash::{MAX_BIN, MAX_DELTA};
+use super::picker::NOISE_FLOOR_FRAMES;
+use super::{FingerprintConfig, FAN_OUT, TARGET_ZONE};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Version of peak picking, pairing and hash encoding, has to be increased on every change
+/// of them that is not covered by configuration or by constants hashed in to `AlgorithmId`
+pub const ALGORITHM_VERSION: u32 = 2;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325; // offset basis of 64 bit FNV-1a hash
+const FNV_PRIME: u64 = 0x0100_0000_01b3; // prime of 64 bit FNV-1a hash
+
+/// Identifier of the algorithm and parameters landmark hashes are calculated with
+///
+/// Landmarks are comparable only if they are calculated with the same identifier.
+/// Parameters hash covers the whole configuration together with constants of pairing
+/// and hash layout, and is stable across platforms and compiler versions,
+/// so it can be stored in the repository next to the fingerprints.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlgorithmId {
+    /// Version of the algorithm
+    pub version: u32,
+    /// FNV-1a hash of all parameters landmarks depend on
+    pub parameters: u64,
+}
+
+impl AlgorithmId {
+    /// Create new instance of AlgorithmId for current version of the algorithm
+    ///
+    /// # Arguments:
+    /// * config - parameters of fingerprint calculation
+    ///
+    /// # Returns new instance of AlgorithmId
+    ///
+    pub fn new(config: &FingerprintConfig) -> Self {
+        let parameters = format!(
+            "{};target_zone={};fan_out={};max_bin={};max_delta={};noise_floor_frames={}",
+            config, TARGET_ZONE, FAN_OUT, MAX_BIN, MAX_DELTA, NOISE_FLOOR_FRAMES
+        );
+        let parameters = parameters.bytes().fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+        Self {
+            version: ALGORITHM_VERSION,
+            parameters,
+        }
+    }
+}
+
+/// Identifier string is stored in the repository
+///
+impl fmt::Display for AlgorithmId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}-{:016x}", self.version, self.parameters)
+    }
+}
+
+impl FromStr for AlgorithmId {
+    type Err = Box<dyn Error>;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.trim_start_matches('v').splitn(2, '-');
+        match (parts.next(), parts.next()) {
+            (Some(version), Some(parameters)) if text.starts_with('v') => Ok(Self {
+                version: version.parse()?,
+                parameters: u64::from_str_radix(parameters, 16)?,
+            }),
+            _ => Err(Box::from(format!("Invalid algorithm identifier: {}", text))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlgorithmId;
+    use crate::fingerprint::FingerprintConfig;
+
+    #[test]
+    fn test_algorithm_id() {
+        let config = FingerprintConfig::default();
+        let id = AlgorithmId::new(&config);
+        assert_eq!(id, AlgorithmId::new(&config.clone()));
+        assert_eq!(id.to_string().parse::<AlgorithmId>().unwrap(), id);
+        // pinned, so changing any default parameter fails here as a reminder that stored hashes are invalidated
+        assert_eq!(id.to_string(), "v2-45887daeed55ad2f");
+        let fuzzed = FingerprintConfig {
+            fuzz_factor: 4,
+            ..config
+        };
+        assert_ne!(AlgorithmId::new(&fuzzed), id);
+        assert!("1-abc".parse::<AlgorithmId>().is_err());
+        assert!("v1-xyz".parse::<AlgorithmId>().is_err());
+    }
+}
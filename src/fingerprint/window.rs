@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+const BLACKMAN_HARRIS: [f32; 4] = [0.35875, 0.48829, 0.14128, 0.01168]; // coefficients of four term Blackman-Harris window
+
+/// Window function applied to each frame before fast fourier transform
+///
+/// Tapering frame edges reduces spectral leakage of frequencies
+/// that do not fit the frame exactly, so peaks stay stable between frames.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowFunction {
+    /// Frame is taken as it is
+    Rectangular,
+    /// Raised cosine reaching zero on frame edges
+    Hann,
+    /// Raised cosine with lower first side lobe, not reaching zero on frame edges
+    Hamming,
+    /// Four term cosine with strongest side lobe suppression
+    BlackmanHarris,
+}
+
+impl WindowFunction {
+    /// Name of the window function used in configuration string
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowFunction::Rectangular => "rectangular",
+            WindowFunction::Hann => "hann",
+            WindowFunction::Hamming => "hamming",
+            WindowFunction::BlackmanHarris => "blackman_harris",
+        }
+    }
+
+    /// Calculates periodic window coefficients that frame samples are multiplied by
+    ///
+    /// # Arguments:
+    /// * size - number of samples in the frame
+    ///
+    /// # Returns collection of coefficients
+    ///
+    pub fn coefficients(&self, size: usize) -> Vec<f32> {
+        (0..size)
+            .map(|n| {
+                let phase = 2.0 * PI * n as f32 / size as f32;
+                match self {
+                    WindowFunction::Rectangular => 1.0,
+                    WindowFunction::Hann => 0.5 - 0.5 * phase.cos(),
+                    WindowFunction::Hamming => 0.54 - 0.46 * phase.cos(),
+                    WindowFunction::BlackmanHarris => {
+                        BLACKMAN_HARRIS[0] - BLACKMAN_HARRIS[1] * phase.cos()
+                            + BLACKMAN_HARRIS[2] * (2.0 * phase).cos()
+                            - BLACKMAN_HARRIS[3] * (3.0 * phase).cos()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for WindowFunction {
+    type Err = Box<dyn Error>;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "rectangular" => Ok(WindowFunction::Rectangular),
+            "hann" => Ok(WindowFunction::Hann),
+            "hamming" => Ok(WindowFunction::Hamming),
+            "blackman_harris" => Ok(WindowFunction::BlackmanHarris),
+            _ => Err(Box::from(format!("Unknown window function: {}", name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WindowFunction;
+
+    #[test]
+    fn test_window_names() {
+        let windows = [
+            WindowFunction::Rectangular,
+            WindowFunction::Hann,
+            WindowFunction::Hamming,
+            WindowFunction::BlackmanHarris,
+        ];
+        for window in windows.iter() {
+            assert_eq!(window.name().parse::<WindowFunction>().unwrap(), *window);
+        }
+        assert!("triangle".parse::<WindowFunction>().is_err());
+    }
+    #[test]
+    fn test_coefficients() {
+        let windows = [
+            (WindowFunction::Rectangular, 1.0),
+            (WindowFunction::Hann, 0.0),
+            (WindowFunction::Hamming, 0.08),
+            (WindowFunction::BlackmanHarris, 0.00006),
+        ];
+        for (window, edge) in windows.iter() {
+            let coefficients = window.coefficients(1024);
+            assert_eq!(coefficients.len(), 1024);
+            assert!((coefficients[0] - edge).abs() < 1e-4);
+            assert!((coefficients[512] - 1.0).abs() < 1e-4);
+            assert!((coefficients[100] - coefficients[924]).abs() < 1e-4);
+        }
+    }
+}
@@ -1,9 +1,98 @@
+mod preprocess;
+mod resample;
+mod sample;
+#[cfg(any(test, feature = "evaluation"))]
+mod synthetic;
+#[cfg(feature = "evaluation")]
+pub(crate) use preprocess::Biquad;
+pub(crate) use preprocess::Filters;
+pub use preprocess::{loudness, Preprocessor};
+pub use resample::{resample, StreamResampler};
+pub use sample::{downmix, Sample};
+#[cfg(any(test, feature = "evaluation"))]
+pub use synthetic::SignalGenerator;
+
+use crate::fingerprint::FingerprintConfig;
+#[cfg(feature = "mp3")]
 use minimp3::{Decoder, Frame};
 use std::error::Error;
+use std::fmt;
+#[cfg(feature = "mp3")]
 use std::fs::File;
+#[cfg(feature = "mp3")]
 use std::io::Read;
 use std::collections::HashMap;
 
+/// Level of decoded samples at 0 dBFS, samples keep the scale of 16 bit integers
+pub const FULL_SCALE: f32 = 32768.0;
+
+/// Decoded mono stream together with its sample rate
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioBuffer {
+    /// Mono samples
+    pub samples: Vec<f32>,
+    /// Number of samples per second
+    pub sample_rate: u32,
+}
+
+impl AudioBuffer {
+    /// Create new instance of AudioBuffer
+    ///
+    /// # Arguments:
+    /// * samples - mono samples
+    /// * sample_rate - number of samples per second
+    ///
+    /// # Returns new instance of AudioBuffer
+    ///
+    pub fn new(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Self {
+            samples,
+            sample_rate,
+        }
+    }
+
+    /// Create new instance of AudioBuffer from PCM samples of any format and number of channels
+    ///
+    /// # Arguments:
+    /// * samples - interleaved samples, integers scaled by their bit depth or floats from -1 to 1
+    /// * channels - number of interleaved channels, mixed down to mono
+    /// * sample_rate - number of samples of a single channel per second
+    ///
+    /// # Returns success of new instance of AudioBuffer, dynamic error if channels do not fit the samples
+    ///
+    pub fn from_interleaved<S: Sample>(
+        samples: &[S],
+        channels: usize,
+        sample_rate: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(downmix(samples, channels)?, sample_rate))
+    }
+
+    /// Duration of the buffer in seconds
+    ///
+    pub fn duration(&self) -> f32 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    /// Resamples buffer to a different sample rate
+    ///
+    /// # Arguments:
+    /// * sample_rate - requested number of samples per second
+    ///
+    /// # Returns new buffer in requested sample rate
+    ///
+    pub fn resample(&self, sample_rate: u32) -> Self {
+        Self {
+            samples: resample(&self.samples, self.sample_rate, sample_rate),
+            sample_rate,
+        }
+    }
+}
+
 /// Chooses the song or acoustic sample that has highest value of matching hashes
 ///
 /// # Arguments:
@@ -24,19 +113,181 @@ pub fn pick_most_likely(findings: &HashMap<String, usize>) -> (String, usize) {
     best_fit
 }
 
+/// Histogram of time offsets between landmarks of the sample and landmarks of songs
+///
+/// Each pair of matching landmarks votes for the difference of its song offset and sample offset.
+/// For a true match most of the votes land in a single offset bin,
+/// while random hash collisions spread across many bins.
+///
+#[derive(Clone, Debug, Default)]
+pub struct OffsetVotes {
+    votes: HashMap<String, HashMap<isize, Alignment>>,
+}
+
+/// Landmarks of the sample aligned with the song at a single offset
+///
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Alignment {
+    /// Number of landmarks aligned at the offset
+    pub(crate) votes: usize,
+    /// Offset of the first aligned landmark in the sample
+    pub(crate) first: usize,
+    /// Offset of the last aligned landmark in the sample
+    pub(crate) last: usize,
+}
+
+/// Best match of the sample against a song located in time
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct SongMatch {
+    /// Author and title of a song in one string
+    pub song: String,
+    /// Number of landmarks aligned at the best offset
+    pub score: usize,
+    /// Time in seconds the matched part starts at in the song
+    pub song_start: f32,
+    /// Time in seconds the matched part ends at in the song
+    pub song_end: f32,
+    /// Time in seconds the matched part starts at in the sample
+    pub sample_start: f32,
+    /// Time in seconds the matched part ends at in the sample
+    pub sample_end: f32,
+}
+
+/// Formats match as song with matched part of it, f.e. "Dark Necessities 1:32-1:42"
+///
+impl fmt::Display for SongMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}-{}",
+            self.song,
+            format_time(self.song_start),
+            format_time(self.song_end)
+        )
+    }
+}
+
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0).round() as usize;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl OffsetVotes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds vote for time alignment of the sample against the song
+    ///
+    /// # Arguments:
+    /// * song - author and title of a song in one string
+    /// * song_offset - offset of the landmark in the song
+    /// * sample_offset - offset of the landmark in the sample
+    ///
+    pub fn vote(&mut self, song: &str, song_offset: usize, sample_offset: usize) {
+        let delta = song_offset as isize - sample_offset as isize;
+        let alignment = Alignment {
+            votes: 1,
+            first: sample_offset,
+            last: sample_offset,
+        };
+        match self.votes.get_mut(song) {
+            Some(histogram) => {
+                histogram
+                    .entry(delta)
+                    .and_modify(|aligned| {
+                        aligned.votes += 1;
+                        aligned.first = aligned.first.min(sample_offset);
+                        aligned.last = aligned.last.max(sample_offset);
+                    })
+                    .or_insert(alignment);
+            }
+            None => {
+                let mut histogram = HashMap::new();
+                histogram.insert(delta, alignment);
+                self.votes.insert(song.to_owned(), histogram);
+            }
+        };
+    }
+
+    /// Calculates score for each song
+    ///
+    /// # Returns hash map with song as a key and height of the highest offset histogram peak as a value
+    ///
+    pub fn scores(&self) -> HashMap<String, usize> {
+        self.votes
+            .keys()
+            .map(|song| {
+                let score = self.best_alignment(song).map(|(_, aligned)| aligned.votes);
+                (song.clone(), score.unwrap_or(0))
+            })
+            .collect()
+    }
+
+    /// Finds the highest offset histogram peak of a song
+    ///
+    /// # Arguments:
+    /// * song - author and title of a song in one string
+    ///
+    /// # Returns Option of the best offset together with landmarks aligned at it,
+    /// None if no landmark of the song voted
+    ///
+    pub(crate) fn best_alignment(&self, song: &str) -> Option<(isize, Alignment)> {
+        // ties are resolved by the lowest offset, so the result does not depend on hash map order
+        self.votes
+            .get(song)?
+            .iter()
+            .max_by_key(|(delta, aligned)| (aligned.votes, -**delta))
+            .map(|(delta, aligned)| (*delta, *aligned))
+    }
+
+    /// Locates the sample in each song at the highest offset histogram peak
+    ///
+    /// # Arguments:
+    /// * config - parameters the landmarks were calculated with, to convert frames to seconds
+    ///
+    /// # Returns collection of matches ordered by score, the best match first
+    ///
+    pub fn song_matches(&self, config: &FingerprintConfig) -> Vec<SongMatch> {
+        let hop = config.hop_size as f32 / config.sample_rate as f32;
+        let window = config.window_size as f32 / config.sample_rate as f32;
+        let mut matches: Vec<SongMatch> = self
+            .votes
+            .keys()
+            .filter_map(|song| {
+                let (delta, aligned) = self.best_alignment(song)?;
+                let song_first = (delta + aligned.first as isize) as f32;
+                let song_last = (delta + aligned.last as isize) as f32;
+                Some(SongMatch {
+                    song: song.clone(),
+                    score: aligned.votes,
+                    song_start: song_first * hop,
+                    song_end: song_last * hop + window,
+                    sample_start: aligned.first as f32 * hop,
+                    sample_end: aligned.last as f32 * hop + window,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.song.cmp(&b.song)));
+        matches
+    }
+}
+
 /// Mp3 decoding file function.
 ///
 /// Decoding is done using `minimp3.`
 /// Samples are read frame by frame and pushed to the vector.
-/// Conversion to mono is done by simply taking the mean of left and right channels.
+/// Conversion to mono is done by taking the mean of left and right channels.
 ///
 /// # Arguments:
 /// * filename - path to the mp3 file we want to decode
 ///
-/// # Returns success of decoded frames, dynamic error otherwise
+/// # Returns success of decoded frames with their sample rate, dynamic error otherwise
 ///
 #[allow(dead_code)]
-pub fn decode_mp3_from_file(filename: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+#[cfg(feature = "mp3")]
+pub fn decode_mp3_from_file(filename: &str) -> Result<AudioBuffer, Box<dyn Error>> {
     let mut decoder = Decoder::new(File::open(filename)?);
     decode_frames(&mut decoder)
 }
@@ -45,46 +296,103 @@ pub fn decode_mp3_from_file(filename: &str) -> Result<Vec<f32>, Box<dyn Error>>
 ///
 /// Decoding is done using `minimp3.`
 /// Samples are read frame by frame and pushed to the vector.
-/// Conversion to mono is done by simply taking the mean of left and right channels.
+/// Conversion to mono is done by taking the mean of left and right channels.
 ///
 /// # Arguments:
 /// * chunk - readable chunk of data encoded in mp3 format
 ///
-/// # Returns success of decoded frames, dynamic error otherwise
+/// # Returns success of decoded frames with their sample rate, dynamic error otherwise
 ///
 #[allow(dead_code)]
-pub fn decode_mp3_from_chunk<R: Read>(chunk: R) -> Result<Vec<f32>, Box<dyn Error>> {
+#[cfg(feature = "mp3")]
+pub fn decode_mp3_from_chunk<R: Read>(chunk: R) -> Result<AudioBuffer, Box<dyn Error>> {
     let mut decoder = Decoder::new(chunk);
     decode_frames(&mut decoder)
 }
 
 #[allow(dead_code)]
-fn decode_frames<R: Read>(decoder: &mut Decoder<R>) -> Result<Vec<f32>, Box<dyn Error>> {
+#[cfg(feature = "mp3")]
+fn decode_frames<R: Read>(decoder: &mut Decoder<R>) -> Result<AudioBuffer, Box<dyn Error>> {
     let mut frames = Vec::new();
+    let mut stream_rate = 0;
     loop {
         match decoder.next_frame() {
-            Ok(Frame { data, channels, .. }) => {
-                if channels < 1 {
-                    return Err(Box::from("Invalid number of channels"));
+            Ok(Frame {
+                data,
+                channels,
+                sample_rate,
+                ..
+            }) => {
+                if sample_rate <= 0 {
+                    return Err(Box::from(format!("Invalid sample rate: {}", sample_rate)));
                 }
-
-                for samples in data.chunks_exact(channels) {
-                    frames.push(f32::from(
-                        samples.iter().fold(0, |sum, x| sum + x / channels as i16),
-                    ));
+                if stream_rate != 0 && stream_rate != sample_rate as u32 {
+                    return Err(Box::from(format!(
+                        "Sample rate changed in the stream from {} to {}",
+                        stream_rate, sample_rate
+                    )));
                 }
+                stream_rate = sample_rate as u32;
+
+                frames.extend(downmix(&data, channels)?);
             }
             Err(minimp3::Error::Eof) => break,
             Err(e) => return Err(Box::from(e)),
         }
     }
-    Ok(frames)
+    Ok(AudioBuffer::new(frames, stream_rate))
 }
 
 #[cfg(test)]
 mod test {
+    #[test]
+    fn test_offset_votes() {
+        let mut votes = super::OffsetVotes::new();
+        for offset in 0..10 {
+            votes.vote("aligned", offset + 100, offset);
+            votes.vote("scattered", offset * 7, offset);
+        }
+        let scores = votes.scores();
+        assert_eq!(scores["aligned"], 10);
+        assert_eq!(scores["scattered"], 1);
+        assert_eq!(
+            super::pick_most_likely(&scores),
+            ("aligned".to_string(), 10)
+        );
+    }
+    #[test]
+    fn test_song_matches() {
+        let config = crate::fingerprint::FingerprintConfig {
+            sample_rate: 1000,
+            window_size: 1000,
+            hop_size: 500,
+            ..Default::default()
+        };
+        let mut votes = super::OffsetVotes::new();
+        for offset in 10..30 {
+            votes.vote("Dark Necessities", offset + 174, offset);
+        }
+        votes.vote("Dark Necessities", 3, 5);
+        votes.vote("Poker face", 40, 12);
+        let matches = votes.song_matches(&config);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(
+            matches[0],
+            super::SongMatch {
+                song: "Dark Necessities".to_string(),
+                score: 20,
+                song_start: 92.0,
+                song_end: 102.5,
+                sample_start: 5.0,
+                sample_end: 15.5,
+            }
+        );
+        assert_eq!(matches[0].to_string(), "Dark Necessities 1:32-1:43");
+        assert_eq!(matches[1].score, 1);
+    }
     #[test]
     #[ignore] // ignored for rust acction test
+    #[cfg(feature = "mp3")]
     fn test_decode_mp3_from_file() {
         // This test verifies if used library for decoding mp3 is working fine
         // and nothing substantial has been changed in external lib.
@@ -92,8 +400,9 @@ mod test {
         let filename = format!("./assets/sample.mp3");
         let decoded_stream = super::decode_mp3_from_file(&filename);
         if let Ok(stream) = decoded_stream {
-            println!("\nDecoded bytes: {:?} \n", &stream.len());
-            assert_eq!(stream.len(), 619776);
+            println!("\nDecoded bytes: {:?} \n", &stream.samples.len());
+            assert_eq!(stream.samples.len(), 619776);
+            assert!(stream.sample_rate > 0);
         } else {
             assert_eq!(1, 2);
         }
@@ -0,0 +1,388 @@
+use super::{AudioBuffer, FULL_SCALE};
+use std::error::Error;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::fmt;
+use std::str::FromStr;
+
+const DC_CUTOFF: f32 = 10.0; // cutoff frequency in Hz of the DC blocker
+const LOUDNESS_BLOCK: f32 = 0.4; // length in seconds of blocks loudness is measured in
+const LOUDNESS_GATE: f32 = -70.0; // dBFS level below which blocks are left out of loudness measurement
+
+/// Preprocessing chain applied to decoded stream before fingerprint calculation
+///
+/// Stages are applied in order: DC blocker, high-pass filter, pre-emphasis
+/// and loudness normalisation, each of them can be switched off.
+/// Normalising quiet recordings and heavily mastered tracks to the same level
+/// makes silence gate and peak magnitudes comparable between them,
+/// while removing DC offset and rumble keeps the lowest bands free of energy
+/// that is not a part of the music.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preprocessor {
+    /// Removes constant offset of samples with a one pole filter
+    pub dc_blocker: bool,
+    /// Cutoff frequency in Hz of second order Butterworth high-pass filter
+    pub high_pass: Option<f32>,
+    /// Coefficient of first order pre-emphasis filter boosting high frequencies, f.e. 0.97
+    pub pre_emphasis: Option<f32>,
+    /// Loudness in dBFS the stream is scaled to, measured as gated root mean square
+    pub target_level: Option<f32>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self {
+            dc_blocker: true,
+            high_pass: Some(80.0),
+            pre_emphasis: None,
+            target_level: Some(-20.0),
+        }
+    }
+}
+
+impl Preprocessor {
+    /// Checks if the chain can be applied to stream of given sample rate
+    ///
+    /// # Arguments:
+    /// * sample_rate - number of samples per second of the stream
+    ///
+    /// # Returns success if the chain is valid, dynamic Error with the reason otherwise
+    ///
+    pub fn validate(&self, sample_rate: u32) -> Result<(), Box<dyn Error>> {
+        if sample_rate == 0 {
+            return Err(Box::from("Invalid sample rate of decoded stream"));
+        }
+        if let Some(cutoff) = self.high_pass {
+            if !(cutoff > 0.0 && cutoff < sample_rate as f32 / 2.0) {
+                return Err(Box::from(format!(
+                    "High-pass cutoff must be between 0 and {} Hz, got {}",
+                    sample_rate / 2,
+                    cutoff
+                )));
+            }
+        }
+        if let Some(coefficient) = self.pre_emphasis {
+            if !(0.0..1.0).contains(&coefficient) {
+                return Err(Box::from(format!(
+                    "Pre-emphasis coefficient must be between 0 and 1, got {}",
+                    coefficient
+                )));
+            }
+        }
+        if let Some(target) = self.target_level {
+            if !(target.is_finite() && target <= 0.0) {
+                return Err(Box::from(format!(
+                    "Target level must be a finite level up to 0 dBFS, got {}",
+                    target
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies preprocessing chain to the stream
+    ///
+    /// # Arguments:
+    /// * decoded_stream - acoustic stream that is decoded to stream of floats with its sample rate
+    ///
+    /// # Returns success of processed stream in the same sample rate, dynamic Error otherwise
+    ///
+    pub fn process(&self, decoded_stream: &AudioBuffer) -> Result<AudioBuffer, Box<dyn Error>> {
+        let sample_rate = decoded_stream.sample_rate;
+        self.validate(sample_rate)?;
+        let mut samples = decoded_stream.samples.clone();
+        self.filters(sample_rate).filter(&mut samples);
+        if let Some(target) = self.target_level {
+            if let Some(level) = loudness(&samples, sample_rate) {
+                let gain = 10_f32.powf((target - level) / 20.0);
+                samples.iter_mut().for_each(|sample| *sample *= gain);
+            }
+        }
+        Ok(AudioBuffer::new(samples, sample_rate))
+    }
+
+    /// Filter stages of the chain, everything but loudness normalisation
+    ///
+    /// Filters keep their state between calls, so a stream filtered in chunks
+    /// gives the same samples as the whole stream filtered at once.
+    /// Chain has to be validated for the sample rate first.
+    ///
+    pub(crate) fn filters(&self, sample_rate: u32) -> Filters {
+        Filters {
+            dc_blocker: if self.dc_blocker {
+                Some(DcBlocker {
+                    pole: 1.0 - 2.0 * PI * DC_CUTOFF / sample_rate as f32,
+                    previous_input: 0.0,
+                    previous_output: 0.0,
+                })
+            } else {
+                None
+            },
+            high_pass: self
+                .high_pass
+                .map(|cutoff| Biquad::high_pass(cutoff, sample_rate)),
+            pre_emphasis: self.pre_emphasis.map(|coefficient| (coefficient, 0.0)),
+        }
+    }
+}
+
+impl fmt::Display for Preprocessor {
+    /// Comma separated list of enabled stages, f.e. `dc_blocker,high_pass:80,target_level:-20`
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut stages = Vec::new();
+        if self.dc_blocker {
+            stages.push("dc_blocker".to_string());
+        }
+        if let Some(cutoff) = self.high_pass {
+            stages.push(format!("high_pass:{}", cutoff));
+        }
+        if let Some(coefficient) = self.pre_emphasis {
+            stages.push(format!("pre_emphasis:{}", coefficient));
+        }
+        if let Some(target) = self.target_level {
+            stages.push(format!("target_level:{}", target));
+        }
+        write!(f, "{}", stages.join(","))
+    }
+}
+
+impl FromStr for Preprocessor {
+    type Err = Box<dyn Error>;
+
+    /// Parses list of stages written by `Display`, stages left out are switched off
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut preprocessor = Self {
+            dc_blocker: false,
+            high_pass: None,
+            pre_emphasis: None,
+            target_level: None,
+        };
+        for stage in s.split(',').filter(|stage| !stage.is_empty()) {
+            let mut parts = stage.splitn(2, ':');
+            let name = parts.next().unwrap_or_default();
+            let value = parts.next().map(str::parse::<f32>).transpose()?;
+            match (name, value) {
+                ("dc_blocker", None) => preprocessor.dc_blocker = true,
+                ("high_pass", Some(cutoff)) => preprocessor.high_pass = Some(cutoff),
+                ("pre_emphasis", Some(coefficient)) => {
+                    preprocessor.pre_emphasis = Some(coefficient)
+                }
+                ("target_level", Some(target)) => preprocessor.target_level = Some(target),
+                _ => return Err(Box::from(format!("Unknown preprocessing stage {}", stage))),
+            }
+        }
+        Ok(preprocessor)
+    }
+}
+
+/// Stateful filter stages of the preprocessing chain
+///
+pub(crate) struct Filters {
+    dc_blocker: Option<DcBlocker>,
+    high_pass: Option<Biquad>,
+    pre_emphasis: Option<(f32, f32)>,
+}
+
+impl Filters {
+    pub(crate) fn filter(&mut self, samples: &mut [f32]) {
+        if let Some(dc_blocker) = self.dc_blocker.as_mut() {
+            dc_blocker.filter(samples);
+        }
+        if let Some(high_pass) = self.high_pass.as_mut() {
+            high_pass.filter(samples);
+        }
+        if let Some((coefficient, previous)) = self.pre_emphasis.as_mut() {
+            for sample in samples.iter_mut() {
+                let input = *sample;
+                *sample = input - *coefficient * *previous;
+                *previous = input;
+            }
+        }
+    }
+}
+
+/// One pole filter removing constant offset of samples
+///
+struct DcBlocker {
+    pole: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl DcBlocker {
+    fn filter(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            let output = *sample - self.previous_input + self.pole * self.previous_output;
+            self.previous_input = *sample;
+            self.previous_output = output;
+            *sample = output;
+        }
+    }
+}
+
+/// Measures loudness of the stream
+///
+/// Stream is split in to blocks, and root mean square is taken over blocks louder than the gate,
+/// so silent parts of the stream do not lower the measured level.
+///
+/// # Returns Option with loudness in dBFS, None if the whole stream is below the gate
+///
+pub fn loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let block = ((sample_rate as f32 * LOUDNESS_BLOCK) as usize).max(1);
+    let gate = FULL_SCALE.powi(2) * 10_f32.powf(LOUDNESS_GATE / 10.0);
+    let (energy, count) = samples
+        .chunks(block)
+        .map(|chunk| {
+            let energy: f64 = chunk.iter().map(|sample| (*sample as f64).powi(2)).sum();
+            (energy, chunk.len())
+        })
+        .filter(|(energy, len)| *energy / *len as f64 >= gate as f64)
+        .fold((0.0, 0), |(total, count), (energy, len)| {
+            (total + energy, count + len)
+        });
+    if count == 0 {
+        return None;
+    }
+    let mean_square = (energy / count as f64) as f32;
+    Some(10.0 * (mean_square / FULL_SCALE.powi(2)).log10())
+}
+
+/// Second order IIR filter in direct form I
+///
+/// Last two inputs and outputs are kept between calls, so the stream can be filtered in chunks.
+///
+pub(crate) struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    state: [f32; 4],
+}
+
+impl Biquad {
+    /// Butterworth high-pass filter from audio EQ cookbook
+    ///
+    pub(crate) fn high_pass(cutoff: f32, sample_rate: u32) -> Self {
+        let omega = 2.0 * PI * cutoff / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * FRAC_1_SQRT_2);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b: [
+                (1.0 + cos) / 2.0 / a0,
+                -(1.0 + cos) / a0,
+                (1.0 + cos) / 2.0 / a0,
+            ],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            state: [0.0; 4],
+        }
+    }
+
+    /// Butterworth low-pass filter from audio EQ cookbook
+    ///
+    #[cfg(feature = "evaluation")]
+    pub(crate) fn low_pass(cutoff: f32, sample_rate: u32) -> Self {
+        let omega = 2.0 * PI * cutoff / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * FRAC_1_SQRT_2);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b: [
+                (1.0 - cos) / 2.0 / a0,
+                (1.0 - cos) / a0,
+                (1.0 - cos) / 2.0 / a0,
+            ],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            state: [0.0; 4],
+        }
+    }
+
+    pub(crate) fn filter(&mut self, samples: &mut [f32]) {
+        let [mut x1, mut x2, mut y1, mut y2] = self.state;
+        for sample in samples.iter_mut() {
+            let x = *sample;
+            let y =
+                self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+            x2 = x1;
+            x1 = x;
+            y2 = y1;
+            y1 = y;
+            *sample = y;
+        }
+        self.state = [x1, x2, y1, y2];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{loudness, AudioBuffer, Preprocessor};
+    use std::f32::consts::PI;
+
+    fn sine(freq: f32, amplitude: f32, offset: f32) -> AudioBuffer {
+        AudioBuffer::new(
+            (0..44100)
+                .map(|t| (2.0 * PI * freq * t as f32 / 44100.0).sin() * amplitude + offset)
+                .collect(),
+            44100,
+        )
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_dc_and_high_pass() {
+        let filters = Preprocessor {
+            target_level: None,
+            ..Preprocessor::default()
+        };
+        let processed = filters.process(&sine(1000.0, 1000.0, 5000.0)).unwrap();
+        let tail = &processed.samples[22050..];
+        assert!((tail.iter().sum::<f32>() / tail.len() as f32).abs() < 1.0);
+        assert!((rms(tail) - 1000.0 / 2_f32.sqrt()).abs() < 10.0);
+        let rumble = filters.process(&sine(20.0, 1000.0, 0.0)).unwrap();
+        assert!(rms(&rumble.samples[22050..]) < 1000.0 / 2_f32.sqrt() / 10.0);
+    }
+    #[test]
+    fn test_pre_emphasis() {
+        let emphasis = Preprocessor {
+            dc_blocker: false,
+            high_pass: None,
+            pre_emphasis: Some(0.97),
+            target_level: None,
+        };
+        let low = emphasis.process(&sine(100.0, 1000.0, 0.0)).unwrap();
+        let high = emphasis.process(&sine(10000.0, 1000.0, 0.0)).unwrap();
+        assert!(rms(&high.samples) > rms(&low.samples) * 10.0);
+        assert!(Preprocessor {
+            pre_emphasis: Some(1.5),
+            ..emphasis
+        }
+        .process(&sine(100.0, 1000.0, 0.0))
+        .is_err());
+    }
+    #[test]
+    fn test_loudness_normalisation() {
+        let preprocessor = Preprocessor::default();
+        let quiet = preprocessor.process(&sine(1000.0, 100.0, 0.0)).unwrap();
+        let loud = preprocessor.process(&sine(1000.0, 30000.0, 0.0)).unwrap();
+        let quiet_level = loudness(&quiet.samples, 44100).unwrap();
+        let loud_level = loudness(&loud.samples, 44100).unwrap();
+        assert!((quiet_level + 20.0).abs() < 0.1);
+        assert!((loud_level + 20.0).abs() < 0.1);
+        // silence is not amplified and does not lower measured level
+        let mut gapped = sine(1000.0, 100.0, 0.0);
+        gapped.samples.extend(vec![0.0; 88200]);
+        let gapped = preprocessor.process(&gapped).unwrap();
+        assert!((loudness(&gapped.samples, 44100).unwrap() + 20.0).abs() < 0.1);
+        assert!(gapped.samples[50000..]
+            .iter()
+            .all(|sample| sample.abs() < 1.0));
+        let silence = AudioBuffer::new(vec![0.0; 44100], 44100);
+        assert_eq!(preprocessor.process(&silence).unwrap(), silence);
+        assert!(preprocessor
+            .process(&AudioBuffer::new(vec![0.0; 10], 0))
+            .is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+const ZERO_CROSSINGS: f64 = 16.0; // number of sinc zero crossings on each side of the interpolated point
+const CUTOFF: f64 = 0.95; // fraction of the lower Nyquist frequency that passes the anti aliasing filter
+
+/// Resamples stream to a different sample rate
+///
+/// Resampling is done by band limited interpolation with Hann windowed sinc kernel.
+/// When sample rate is lowered the kernel is stretched,
+/// so frequencies above the new Nyquist frequency are filtered out instead of aliased.
+/// Output samples are calculated in parallel.
+///
+/// # Arguments:
+/// * samples - mono stream of samples
+/// * from - sample rate of the stream
+/// * to - requested sample rate
+///
+/// # Returns stream of samples in requested sample rate
+///
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let interpolator = Interpolator::new(from, to);
+    (0..interpolator.length(samples.len()))
+        .into_par_iter()
+        .map(|n| interpolator.sample(n, samples, 0))
+        .collect()
+}
+
+/// Resamples stream delivered in chunks of any size to a different sample rate
+///
+/// Input samples are kept until the whole kernel of each output sample is received,
+/// and output samples are counted from the beginning of the stream,
+/// so the output is exactly the same as `resample` of the whole stream at once.
+///
+#[derive(Clone, Debug)]
+pub struct StreamResampler {
+    /// Kernel parameters, None if sample rates are equal and samples pass through
+    interpolator: Option<Interpolator>,
+    /// Input samples that are still needed by next output samples
+    buffer: Vec<f32>,
+    /// Index of the first buffered sample in the input stream
+    start: usize,
+    /// Index of the next output sample in the output stream
+    next: usize,
+}
+
+impl StreamResampler {
+    /// Create new instance of StreamResampler
+    ///
+    /// # Arguments:
+    /// * from - sample rate of the stream
+    /// * to - requested sample rate
+    ///
+    /// # Returns new instance of StreamResampler
+    ///
+    pub fn new(from: u32, to: u32) -> Self {
+        Self {
+            interpolator: if from == to {
+                None
+            } else {
+                Some(Interpolator::new(from, to))
+            },
+            buffer: Vec::new(),
+            start: 0,
+            next: 0,
+        }
+    }
+
+    /// Feeds resampler with the next chunk of the stream
+    ///
+    /// # Arguments:
+    /// * samples - next samples of the stream in its sample rate
+    ///
+    /// # Returns output samples which whole kernel is received so far
+    ///
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        let interpolator = match self.interpolator {
+            Some(interpolator) => interpolator,
+            None => return samples.to_vec(),
+        };
+        self.buffer.extend_from_slice(samples);
+        let received = self.start + self.buffer.len();
+        let mut end = self.next;
+        while interpolator.last_input(end) < received {
+            end += 1;
+        }
+        let output = self.interpolate(self.next..end);
+        self.next = end;
+        let needed = interpolator.first_input(self.next).min(received);
+        if needed > self.start {
+            self.buffer.drain(..needed - self.start);
+            self.start = needed;
+        }
+        output
+    }
+
+    /// Ends the stream
+    ///
+    /// # Returns output samples left, which kernel is cut by the end of the stream
+    ///
+    pub fn finish(&mut self) -> Vec<f32> {
+        let interpolator = match self.interpolator {
+            Some(interpolator) => interpolator,
+            None => return Vec::new(),
+        };
+        let end = interpolator.length(self.start + self.buffer.len());
+        let output = self.interpolate(self.next..end.max(self.next));
+        self.next = end.max(self.next);
+        output
+    }
+
+    fn interpolate(&self, range: std::ops::Range<usize>) -> Vec<f32> {
+        match (&self.interpolator, self.buffer.is_empty()) {
+            (Some(interpolator), false) => range
+                .into_par_iter()
+                .map(|n| interpolator.sample(n, &self.buffer, self.start))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Band limited interpolation between two sample rates
+///
+#[derive(Clone, Copy, Debug)]
+struct Interpolator {
+    /// Number of input samples per output sample
+    ratio: f64,
+    /// Cutoff frequency relative to the input sample rate
+    cutoff: f64,
+    /// Half width of the kernel in input samples
+    half_width: f64,
+}
+
+impl Interpolator {
+    fn new(from: u32, to: u32) -> Self {
+        let ratio = from as f64 / to as f64;
+        let cutoff = CUTOFF * (1.0 / ratio).min(1.0);
+        Self {
+            ratio,
+            cutoff,
+            half_width: ZERO_CROSSINGS / cutoff,
+        }
+    }
+
+    /// Number of output samples of the input stream of given length
+    ///
+    fn length(&self, input_length: usize) -> usize {
+        (input_length as f64 / self.ratio).floor() as usize
+    }
+
+    /// Index of the first input sample in the kernel of output sample n
+    ///
+    fn first_input(&self, n: usize) -> usize {
+        (n as f64 * self.ratio - self.half_width).ceil().max(0.0) as usize
+    }
+
+    /// Index of the last input sample in the kernel of output sample n
+    ///
+    fn last_input(&self, n: usize) -> usize {
+        (n as f64 * self.ratio + self.half_width).floor() as usize
+    }
+
+    /// Output sample n, kernel is cut by the input samples available
+    ///
+    /// # Arguments:
+    /// * n - index of the output sample in the output stream
+    /// * samples - input samples holding the whole kernel, or the end of the stream
+    /// * start - index of the first of samples in the input stream
+    ///
+    fn sample(&self, n: usize, samples: &[f32], start: usize) -> f32 {
+        let center = n as f64 * self.ratio;
+        let first = self.first_input(n).max(start);
+        let last = self.last_input(n).min(start + samples.len() - 1);
+        let mut sum = 0.0;
+        for idx in first..=last {
+            let distance = idx as f64 - center;
+            sum += samples[idx - start] as f64 * kernel(distance, self.cutoff, self.half_width);
+        }
+        sum as f32
+    }
+}
+
+/// Hann windowed sinc scaled to pass the cutoff frequency with unity gain
+///
+fn kernel(distance: f64, cutoff: f64, half_width: f64) -> f64 {
+    let window = 0.5 + 0.5 * (PI * distance / half_width).cos();
+    let x = PI * cutoff * distance;
+    let sinc = if x.abs() < 1e-9 { 1.0 } else { x.sin() / x };
+    cutoff * sinc * window
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use std::f32::consts::PI;
+
+    fn sine(freq: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|t| (2.0 * PI * freq * t as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_resample_keeps_tone() {
+        let resampled = super::resample(&sine(1000.0, 44100, 44100), 44100, 11025);
+        assert_eq!(resampled.len(), 11025);
+        let expected = sine(1000.0, 11025, 11025);
+        for (sample, expected) in resampled.iter().zip(expected.iter()).skip(100).take(10800) {
+            assert!((sample - expected).abs() < 0.01);
+        }
+    }
+    #[test]
+    fn test_resample_filters_aliases() {
+        let resampled = super::resample(&sine(8000.0, 44100, 44100), 44100, 11025);
+        let energy: f32 = resampled[100..10900].iter().map(|s| s * s).sum();
+        assert!((energy / 10800.0).sqrt() < 0.01);
+    }
+    #[test]
+    fn test_resample_up() {
+        let resampled = super::resample(&sine(440.0, 8000, 8000), 8000, 11025);
+        assert_eq!(resampled.len(), 11025);
+        let expected = sine(440.0, 11025, 11025);
+        for (sample, expected) in resampled.iter().zip(expected.iter()).skip(100).take(10800) {
+            assert!((sample - expected).abs() < 0.01);
+        }
+    }
+    #[test]
+    fn test_stream_resampler_equals_whole_stream() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let stream: Vec<f32> = (0..30_000).map(|_| rng.gen::<f32>() * 2.0 - 1.0).collect();
+        for (from, to) in [
+            (44100, 11025),
+            (48000, 11025),
+            (8000, 11025),
+            (11025, 11025),
+        ]
+        .iter()
+        {
+            let mut resampler = super::StreamResampler::new(*from, *to);
+            let mut resampled = Vec::new();
+            let mut position = 0;
+            while position < stream.len() {
+                let end = (position + rng.gen_range(1, 700)).min(stream.len());
+                resampled.extend(resampler.push(&stream[position..end]));
+                position = end;
+            }
+            resampled.extend(resampler.finish());
+            assert_eq!(resampled, super::resample(&stream, *from, *to));
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use super::FULL_SCALE;
+use std::error::Error;
+
+/// Single PCM sample of any common format
+///
+/// Integer samples are scaled by their bit depth and float samples are expected
+/// in range from -1 to 1, so every format is converted to the same scale
+/// of decoded samples and gives the same fingerprints.
+///
+pub trait Sample: Copy {
+    /// Value of the sample in the scale of decoded samples, where 0 dBFS is `FULL_SCALE`
+    fn to_scaled(self) -> f32;
+}
+
+impl Sample for i16 {
+    fn to_scaled(self) -> f32 {
+        f32::from(self)
+    }
+}
+
+impl Sample for i32 {
+    fn to_scaled(self) -> f32 {
+        (f64::from(self) / 65536.0) as f32
+    }
+}
+
+impl Sample for f32 {
+    fn to_scaled(self) -> f32 {
+        self * FULL_SCALE
+    }
+}
+
+impl Sample for f64 {
+    fn to_scaled(self) -> f32 {
+        (self * f64::from(FULL_SCALE)) as f32
+    }
+}
+
+/// Converts interleaved samples to mono samples in the scale of decoded samples
+///
+/// Conversion to mono is done by taking the mean of all channels of each frame,
+/// calculated in floating point, so quiet samples are not rounded away.
+///
+/// # Arguments:
+/// * samples - interleaved samples, first sample of each channel followed by the second ones and so on
+/// * channels - number of interleaved channels
+///
+/// # Returns success of mono samples, dynamic error if there is no channel or the last frame is incomplete
+///
+pub fn downmix<S: Sample>(samples: &[S], channels: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+    if channels < 1 {
+        return Err(Box::from("Invalid number of channels"));
+    }
+    let frames = samples.chunks_exact(channels);
+    if !frames.remainder().is_empty() {
+        return Err(Box::from(format!(
+            "Number of samples {} is not a multiple of number of channels {}",
+            samples.len(),
+            channels
+        )));
+    }
+    Ok(frames
+        .map(|frame| frame.iter().map(|sample| sample.to_scaled()).sum::<f32>() / channels as f32)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downmix, Sample, FULL_SCALE};
+
+    #[test]
+    fn test_sample_formats() {
+        assert_eq!(i16::MIN.to_scaled(), -FULL_SCALE);
+        assert_eq!(i32::MIN.to_scaled(), -FULL_SCALE);
+        assert_eq!((-1.0_f32).to_scaled(), -FULL_SCALE);
+        assert_eq!((-1.0_f64).to_scaled(), -FULL_SCALE);
+        assert_eq!(0.5_f32.to_scaled(), 16384_i16.to_scaled());
+        assert_eq!((1 << 30_i32).to_scaled(), 16384_i16.to_scaled());
+
+        // mean of odd samples is kept instead of truncated per channel
+        assert_eq!(downmix(&[1_i16, 2, -3, -4], 2).unwrap(), vec![1.5, -3.5]);
+        assert_eq!(
+            downmix(&[0.5_f64, 0.5, 0.5, 0.0, 0.0, 0.0], 3).unwrap(),
+            vec![0.5 * FULL_SCALE, 0.0]
+        );
+        assert!(downmix(&[1_i16, 2, 3], 2).is_err());
+        assert!(downmix(&[1_i16, 2], 0).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use super::{AudioBuffer, FULL_SCALE};
+use rand::prelude::*;
+use std::f32::consts::PI;
+
+const SCALE: [usize; 7] = [0, 2, 4, 5, 7, 9, 11]; // semitones of major scale notes of a song are picked from
+const HARMONICS: [f32; 3] = [1.0, 0.5, 0.25]; // amplitudes of harmonics of a single note
+const RHYTHM: [f32; 5] = [0.5, 1.0, 1.0, 1.5, 2.0]; // lengths of notes in beats
+const NOISE_LEVEL: f32 = 0.02; // level of background noise of a song relative to its peak
+
+/// Generator of deterministic synthetic audio
+///
+/// Signals are calculated from the seed only, so tests and evaluation can verify
+/// fingerprinting and matching on any machine without copyrighted recordings.
+/// Level of each signal is its peak amplitude relative to full scale of decoded samples.
+///
+pub struct SignalGenerator {
+    /// Generator of random frequencies, notes and noise
+    rng: StdRng,
+    /// Sample rate of generated signals
+    sample_rate: u32,
+}
+
+impl SignalGenerator {
+    /// Create new instance of SignalGenerator
+    ///
+    /// # Arguments:
+    /// * seed - seed of random parts of signals, the same seed gives the same signals
+    /// * sample_rate - number of samples per second of generated signals
+    ///
+    /// # Returns new instance of SignalGenerator
+    ///
+    pub fn new(seed: u64, sample_rate: u32) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            sample_rate,
+        }
+    }
+
+    /// Silence lasting duration seconds
+    ///
+    pub fn silence(&self, duration: f32) -> AudioBuffer {
+        AudioBuffer::new(vec![0.0; self.length(duration)], self.sample_rate)
+    }
+
+    /// Sine wave of frequency in Hz lasting duration seconds
+    ///
+    pub fn sine(&self, frequency: f32, duration: f32, level: f32) -> AudioBuffer {
+        self.chord(&[frequency], duration, level)
+    }
+
+    /// Sine sweep with frequency rising exponentially from start to end in Hz,
+    /// so each octave lasts the same time
+    ///
+    pub fn sweep(&self, start: f32, end: f32, duration: f32, level: f32) -> AudioBuffer {
+        let rate = (end / start).ln() / duration;
+        let samples = (0..self.length(duration))
+            .map(|t| {
+                let time = t as f32 / self.sample_rate as f32;
+                let phase = 2.0 * PI * start * ((rate * time).exp() - 1.0) / rate;
+                phase.sin() * level * FULL_SCALE
+            })
+            .collect();
+        AudioBuffer::new(samples, self.sample_rate)
+    }
+
+    /// Sum of sine waves of frequencies in Hz, each of them at the same amplitude
+    ///
+    pub fn chord(&self, frequencies: &[f32], duration: f32, level: f32) -> AudioBuffer {
+        let amplitude = level * FULL_SCALE / frequencies.len().max(1) as f32;
+        let samples = (0..self.length(duration))
+            .map(|t| {
+                frequencies
+                    .iter()
+                    .map(|frequency| {
+                        (2.0 * PI * frequency * t as f32 / self.sample_rate as f32).sin()
+                    })
+                    .sum::<f32>()
+                    * amplitude
+            })
+            .collect();
+        AudioBuffer::new(samples, self.sample_rate)
+    }
+
+    /// White noise with uniform distribution of samples
+    ///
+    pub fn white_noise(&mut self, duration: f32, level: f32) -> AudioBuffer {
+        let amplitude = level * FULL_SCALE;
+        let samples = (0..self.length(duration))
+            .map(|_| (self.rng.gen::<f32>() * 2.0 - 1.0) * amplitude)
+            .collect();
+        AudioBuffer::new(samples, self.sample_rate)
+    }
+
+    /// Pink noise with equal energy in every octave, made by Paul Kellett's economy filter
+    ///
+    pub fn pink_noise(&mut self, duration: f32, level: f32) -> AudioBuffer {
+        let (mut b0, mut b1, mut b2) = (0.0, 0.0, 0.0);
+        let samples: Vec<f32> = (0..self.length(duration))
+            .map(|_| {
+                let white = self.rng.gen::<f32>() * 2.0 - 1.0;
+                b0 = 0.99765 * b0 + white * 0.0990460;
+                b1 = 0.96300 * b1 + white * 0.2965164;
+                b2 = 0.57000 * b2 + white * 1.0526913;
+                b0 + b1 + b2 + white * 0.1848
+            })
+            .collect();
+        AudioBuffer::new(normalise(samples, level), self.sample_rate)
+    }
+
+    /// Sequence of sine tones of random frequencies between 400 and 3000 Hz
+    ///
+    /// # Arguments:
+    /// * tones - number of tones
+    /// * tone_duration - seconds of a single tone
+    /// * level - peak amplitude relative to full scale
+    ///
+    pub fn tone_sequence(&mut self, tones: usize, tone_duration: f32, level: f32) -> AudioBuffer {
+        let tone_length = self.length(tone_duration);
+        let mut samples = Vec::with_capacity(tones * tone_length);
+        for _ in 0..tones {
+            let frequency = self.rng.gen_range(400.0, 3000.0);
+            samples.extend((0..tone_length).map(|t| {
+                (2.0 * PI * frequency * t as f32 / self.sample_rate as f32).sin()
+                    * level
+                    * FULL_SCALE
+            }));
+        }
+        AudioBuffer::new(samples, self.sample_rate)
+    }
+
+    /// Song emulated by melody and bass line played in a random key and tempo
+    ///
+    /// Notes are picked from major scale and have harmonics and decaying envelope,
+    /// with quiet background noise mixed in, so each seed gives a distinct song
+    /// that has spectral structure of real music.
+    ///
+    /// # Arguments:
+    /// * duration - seconds of the song
+    /// * level - peak amplitude relative to full scale
+    ///
+    pub fn song(&mut self, duration: f32, level: f32) -> AudioBuffer {
+        // key is not tuned to any common pitch, so different songs rarely share frequencies
+        let root = 110.0 * 2_f32.powf(self.rng.gen::<f32>());
+        let beat = self.rng.gen_range(0.15, 0.3);
+        let length = self.length(duration);
+        let mut samples = Vec::with_capacity(length + self.length(beat * 2.0));
+        let mut bass = root;
+        let mut note = 0;
+        while samples.len() < length {
+            if note % 4 == 0 {
+                bass = root * [1.0, 1.5, 4.0 / 3.0][self.rng.gen_range(0, 3)];
+            }
+            let degree = self.rng.gen_range(0, 2 * SCALE.len());
+            let semitone = SCALE[degree % SCALE.len()] + 12 * (degree / SCALE.len());
+            let melody = 4.0 * root * 2_f32.powf(semitone as f32 / 12.0);
+            let note_duration = beat * RHYTHM[self.rng.gen_range(0, RHYTHM.len())];
+            let note_length = self.length(note_duration);
+            samples.extend((0..note_length).map(|t| {
+                let time = t as f32 / self.sample_rate as f32;
+                let decay = t as f32 / note_length as f32;
+                HARMONICS
+                    .iter()
+                    .enumerate()
+                    .map(|(harmonic, amplitude)| {
+                        let harmonic = (harmonic + 1) as f32;
+                        ((2.0 * PI * melody * harmonic * time).sin() * (-3.0 * decay).exp()
+                            + (2.0 * PI * bass * harmonic * time).sin() * (-decay).exp() * 0.5)
+                            * amplitude
+                    })
+                    .sum::<f32>()
+            }));
+            note += 1;
+        }
+        samples.truncate(length);
+        let mut samples = normalise(samples, level * (1.0 - NOISE_LEVEL));
+        let noise = self.white_noise(duration, level * NOISE_LEVEL);
+        for (sample, noise) in samples.iter_mut().zip(noise.samples.iter()) {
+            *sample += noise;
+        }
+        AudioBuffer::new(samples, self.sample_rate)
+    }
+
+    /// Number of samples lasting duration seconds
+    ///
+    fn length(&self, duration: f32) -> usize {
+        (duration.max(0.0) * self.sample_rate as f32) as usize
+    }
+}
+
+/// Scales samples so their peak amplitude is level relative to full scale
+///
+fn normalise(mut samples: Vec<f32>, level: f32) -> Vec<f32> {
+    let peak = samples
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    if peak > 0.0 {
+        let gain = level * FULL_SCALE / peak;
+        samples.iter_mut().for_each(|sample| *sample *= gain);
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignalGenerator, FULL_SCALE};
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn test_signals() {
+        let mut generator = SignalGenerator::new(1, 11025);
+        assert_eq!(generator.silence(1.0).samples, vec![0.0; 11025]);
+        let sine = generator.sine(1000.0, 0.5, 0.5);
+        assert_eq!(sine.samples.len(), 5512);
+        assert!((peak(&sine.samples) - 0.5 * FULL_SCALE).abs() < 0.01 * FULL_SCALE);
+        let sweep = generator.sweep(100.0, 4000.0, 2.0, 0.5);
+        let crossings = |samples: &[f32]| {
+            samples
+                .windows(2)
+                .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+                .count()
+        };
+        // the last tenth of a second of the sweep is close to the end frequency
+        assert!(crossings(&sweep.samples[..1102]) < 20);
+        assert!(crossings(&sweep.samples[sweep.samples.len() - 1102..]) > 300);
+        let chord = generator.chord(&[440.0, 550.0, 660.0], 1.0, 0.9);
+        assert!(peak(&chord.samples) <= 0.9 * FULL_SCALE);
+        for noise in [
+            generator.white_noise(1.0, 0.1),
+            generator.pink_noise(1.0, 0.1),
+        ]
+        .iter()
+        {
+            assert!((peak(&noise.samples) - 0.1 * FULL_SCALE).abs() < 0.01 * FULL_SCALE);
+        }
+        let song = generator.song(3.0, 0.8);
+        assert_eq!(song.samples.len(), 3 * 11025);
+        assert!(peak(&song.samples) <= 0.8 * FULL_SCALE);
+        assert!(peak(&song.samples) > 0.75 * FULL_SCALE);
+    }
+    #[test]
+    fn test_reproducible() {
+        let generate = |seed| {
+            let mut generator = SignalGenerator::new(seed, 22050);
+            let mut samples = generator.tone_sequence(5, 0.1, 0.3).samples;
+            samples.extend(generator.white_noise(0.1, 0.1).samples);
+            samples.extend(generator.song(1.0, 0.5).samples);
+            samples
+        };
+        assert_eq!(generate(4), generate(4));
+        assert_ne!(generate(4), generate(5));
+    }
+}
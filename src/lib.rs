@@ -1,44 +1,54 @@
-mod data;
-mod fingerprint;
-mod helpers;
+pub mod data;
+#[cfg(feature = "evaluation")]
+pub mod evaluation;
+pub mod fingerprint;
+pub mod helpers;
 
-use dotenv;
+// environment of tests talking to redis and radio streams
+#[cfg(all(test, any(feature = "redis", feature = "stream")))]
 #[macro_use]
 extern crate dotenv_codegen;
 
 #[cfg(test)]
 mod tests {
+    use super::data::memory_actions::MemoryRepository;
+    #[cfg(all(feature = "redis", feature = "mp3"))]
     use super::data::redis_actions::RedisHelper;
+    #[cfg(feature = "stream")]
     use super::data::stream_actions::ArcStreamListener;
     use super::data::Repository;
-    use super::fingerprint::FingerprintHandle;
-    use super::helpers::decode_mp3_from_file;
-    use super::helpers::pick_most_likely;
+    #[cfg(feature = "stream")]
+    use super::fingerprint::StreamingFingerprinter;
+    use super::fingerprint::{FingerprintConfig, FingerprintHandle};
+    #[cfg(all(feature = "redis", feature = "mp3"))]
+    use super::helpers::{decode_mp3_from_file, pick_most_likely};
+    use super::helpers::{AudioBuffer, SignalGenerator};
+    #[cfg(feature = "stream")]
     use futures_await_test::async_test;
+    #[cfg(feature = "stream")]
     use std::thread;
+    #[cfg(feature = "stream")]
     use std::thread::sleep;
+    #[cfg(feature = "stream")]
     use std::time::Duration;
+    #[cfg(feature = "mp3")]
     use std::time::Instant;
+    #[cfg(feature = "stream")]
     use tokio::runtime::Runtime;
 
     #[test]
     #[ignore] // ignored for rust acction test
+    #[cfg(feature = "mp3")]
     fn test_calc_fingerprint_collection_short() {
         let start_time = Instant::now();
-        let fingerprint_handler = super::fingerprint::FingerprintHandle::new();
+        let fingerprint_handler = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
         let fingerprint_collection = fingerprint_handler
             .calc_fingerprint_collection(
                 &super::helpers::decode_mp3_from_file(&format!("./assets/sample.mp3")).unwrap(),
             )
             .unwrap();
-        for fingerprint in fingerprint_collection.iter() {
-            if *fingerprint != 0 {
-                let fingerprint_log10 = (*fingerprint as f64).log10();
-                assert_eq!(
-                    fingerprint_log10 > 12_f64 && fingerprint_log10 < 13_f64,
-                    true
-                );
-            }
+        for landmarks in fingerprint_collection.windows(2) {
+            assert!(landmarks[0].offset <= landmarks[1].offset);
         }
         println!(
             "\nDecoding and hashing stream took {} milliseconds\n",
@@ -51,9 +61,10 @@ mod tests {
     }
     #[test]
     #[ignore] // ignored for rust acction test
+    #[cfg(feature = "mp3")]
     fn test_calc_fingerprint_collection_long() {
         let start_time = Instant::now();
-        let fingerprint_handler = super::fingerprint::FingerprintHandle::new();
+        let fingerprint_handler = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
         let fingerprint_collection = fingerprint_handler
             .calc_fingerprint_collection(
                 &super::helpers::decode_mp3_from_file(&format!(
@@ -62,14 +73,8 @@ mod tests {
                 .unwrap(),
             )
             .unwrap();
-        for fingerprint in fingerprint_collection.iter() {
-            if *fingerprint != 0 {
-                let fingerprint_log10 = (*fingerprint as f64).log10();
-                assert_eq!(
-                    fingerprint_log10 > 12_f64 && fingerprint_log10 < 13_f64,
-                    true
-                );
-            }
+        for landmarks in fingerprint_collection.windows(2) {
+            assert!(landmarks[0].offset <= landmarks[1].offset);
         }
         println!(
             "\nDecoding and hashing stream took {} milliseconds\n",
@@ -83,11 +88,13 @@ mod tests {
 
     #[test]
     #[ignore] // ignored for rust acction test
+    #[cfg(all(feature = "redis", feature = "mp3"))]
     fn test_matching_algorithm() {
         dotenv::dotenv().ok();
         if dotenv!("REDIS_ENABLED") == "true" {
-            let fingerprint_handle = FingerprintHandle::new();
             let mut redis = RedisHelper::new(&"redis://127.0.0.1/").unwrap();
+            let config = redis.load_config().unwrap().unwrap_or_default();
+            let fingerprint_handle = FingerprintHandle::new(config).unwrap();
             let path = "./assets/";
             let sample = "sample.mp3";
             let files = [
@@ -107,7 +114,13 @@ mod tests {
                     let fingerprints = fingerprint_handle
                         .calc_fingerprint_collection(&decoded)
                         .unwrap();
-                    redis.store(&fingerprints, &format!("{}", file)).unwrap();
+                    redis
+                        .store(
+                            fingerprint_handle.config(),
+                            &fingerprints,
+                            &format!("{}", file),
+                        )
+                        .unwrap();
                 }
             }
             let path = format!("{}{}", &path, &sample);
@@ -115,17 +128,58 @@ mod tests {
             let fingerprints = fingerprint_handle
                 .calc_fingerprint_collection(&decoded)
                 .unwrap();
-            let findings = redis.find_matches(&fingerprints).unwrap();
+            let findings = redis
+                .find_matches(fingerprint_handle.config(), &fingerprints)
+                .unwrap();
             let best_match = pick_most_likely(&findings);
             println!("\n Found best match: {:?} with score {:?} \n", &best_match.0, &best_match.1);
             assert_eq!(best_match.0, files[7]);
+            let located = redis
+                .locate_matches(fingerprint_handle.config(), &fingerprints)
+                .unwrap();
+            println!("\n Sample is located at {} \n", &located[0]);
+            assert_eq!(located[0].song, best_match.0);
         } else {
             println!("test_matching_algorithm does nothing");
         }
     }
 
+    #[test]
+    fn test_matching_synthetic_songs() {
+        let handle = FingerprintHandle::new(FingerprintConfig::default()).unwrap();
+        let sample_rate = handle.config().sample_rate;
+        let mut repository = MemoryRepository::new();
+        let songs: Vec<AudioBuffer> = (0..5)
+            .map(|seed| SignalGenerator::new(seed, sample_rate).song(30.0, 0.5))
+            .collect();
+        for (idx, song) in songs.iter().enumerate() {
+            let fingerprints = handle.calc_fingerprint_collection(song).unwrap();
+            repository
+                .store(handle.config(), &fingerprints, &format!("song {}", idx))
+                .unwrap();
+        }
+        // 8 seconds of the fourth song starting at 12 second, recorded with background noise
+        let start = 12 * sample_rate as usize;
+        let mut sample = songs[3].samples[start..start + 8 * sample_rate as usize].to_vec();
+        let noise = SignalGenerator::new(99, sample_rate).pink_noise(8.0, 0.1);
+        for (sample, noise) in sample.iter_mut().zip(noise.samples.iter()) {
+            *sample += noise;
+        }
+        let fingerprints = handle
+            .calc_fingerprint_collection(&AudioBuffer::new(sample, sample_rate))
+            .unwrap();
+        let located = repository
+            .locate_matches(handle.config(), &fingerprints)
+            .unwrap();
+        println!("\n Sample is located at {} \n", &located[0]);
+        assert_eq!(located[0].song, "song 3");
+        assert!((located[0].song_start - located[0].sample_start - 12.0).abs() < 0.1);
+        assert!(located[0].score > located[1].score * 5);
+    }
+
     #[async_test]
     // #[ignore]
+    #[cfg(feature = "stream")]
     async fn test_stream_listener_mp3_fingerprints() {
         dotenv::dotenv().ok();
         if dotenv!("RADIO_STREAM_ENABLED") == "true" {
@@ -134,38 +188,32 @@ mod tests {
             ).unwrap();
             let receiver = listener.get_listener();
             let reader = thread::spawn(move || {
-                let fingerprint_handler = super::fingerprint::FingerprintHandle::new();
+                let mut fingerprinter: Option<StreamingFingerprinter> = None;
                 let mut collected_num = 0;
-                let mut collected = Vec::new();
                 loop {
                     if collected_num > 5 {
                         break;
                     }
                     let start_time = Instant::now();
-                    let mut decoded = receiver.recv().unwrap();
-                    if collected_num % 5 == 0 {
-                        collected.append(&mut decoded);
-                    } else {
-                        collected.append(&mut decoded);
-                        let fingerprint_collection = fingerprint_handler
-                            .calc_fingerprint_collection(&collected)
-                            .unwrap();
-                        for fingerprint in fingerprint_collection.iter() {
-                            if *fingerprint != 0 {
-                                let fingerprint_log10 = (*fingerprint as f64).log10();
-                                println!("\nFingerprint for stream: {:?}", &fingerprint);
-                                assert_eq!(
-                                    fingerprint_log10 > 12_f64 && fingerprint_log10 < 13_f64,
-                                    true
-                                );
-                            }
-                        }
-                        collected.clear();
-                        println!(
-                            "\nDownloading, decoding, pipping and hashing stream mp3 chunk from internet radio took {} milliseconds\n",
-                            start_time.elapsed().as_millis()
-                        );
+                    let decoded = receiver.recv().unwrap();
+                    // resampler state is carried across chunks, sample rate is known from the first one
+                    let fingerprinter = fingerprinter.get_or_insert_with(|| {
+                        StreamingFingerprinter::with_sample_rate(
+                            FingerprintConfig::default(),
+                            decoded.sample_rate,
+                        )
+                        .unwrap()
+                    });
+                    let fingerprint_collection = fingerprinter.push(&decoded.samples);
+                    for landmarks in fingerprint_collection.windows(2) {
+                        println!("\nFingerprint for stream: {:?}", &landmarks[0]);
+                        assert!(landmarks[0].offset <= landmarks[1].offset);
                     }
+                    println!(
+                        "\nDownloading, decoding, pipping and hashing stream mp3 chunk from internet radio took {} milliseconds, stream time {} seconds\n",
+                        start_time.elapsed().as_millis(),
+                        fingerprinter.timestamp()
+                    );
                     collected_num += 1;
                 }
             });